@@ -0,0 +1,91 @@
+use crate::{BotConfig, BotResult, check_slack_response, slack_client, with_retries};
+
+/// A channel as listed by conversations.list
+struct Channel {
+    id: String,
+    name: String,
+    private: bool,
+    member: bool,
+}
+
+/**
+ * List all non-archived channels visible to the section's token.
+ * Needs the `channels:read` (and `groups:read` for private channels) scope.
+ */
+fn list_channels(conf: &BotConfig) -> BotResult<Vec<Channel>>
+{
+    let mut channels = Vec::new();
+    let mut cursor = String::new();
+    loop {
+        let json = with_retries(conf, || {
+            let _permit = conf.http_semaphore.acquire();
+            let mut params = vec![("types", "public_channel,private_channel"), ("exclude_archived", "true"), ("limit", "1000")];
+            if !cursor.is_empty() {
+                params.push(("cursor", cursor.as_str()));
+            }
+            let res = slack_client(&conf.extra_headers).build()?
+                .get("https://slack.com/api/conversations.list")
+                .query(&params)
+                .bearer_auth(conf.slack_token.get())
+                .send()?;
+            check_slack_response(res)
+        })?;
+        for c in json["channels"].as_array().into_iter().flatten() {
+            if let (Some(id), Some(name)) = (c["id"].as_str(), c["name"].as_str()) {
+                channels.push(Channel {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    private: c["is_private"].as_bool().unwrap_or(false),
+                    member: c["is_member"].as_bool().unwrap_or(false),
+                });
+            }
+        }
+        cursor = json["response_metadata"]["next_cursor"].as_str().unwrap_or("").to_string();
+        if cursor.is_empty() {
+            break;
+        }
+    }
+    Ok(channels)
+}
+
+/**
+ * Print the channels a section's token can see, with IDs and whether
+ * the bot is a member (needed for private channels, and for public ones
+ * unless the token has `chat:write.public`).
+ *
+ * @param conf Section whose token to use
+ * @param filter Only channels whose name matches this glob pattern, or
+ *  contains it if it has no wildcards
+ * @param json Print a JSON array instead of a table
+ */
+pub fn run(conf: &BotConfig, filter: Option<&str>, json: bool) -> BotResult<()>
+{
+    let pattern = filter.filter(|f| f.contains(['*', '?', '[']))
+        .map(glob::Pattern::new)
+        .transpose()
+        .map_err(|e| anyhow::anyhow!("Invalid --filter pattern: {}", e))?;
+    let mut channels: Vec<Channel> = list_channels(conf)?.into_iter()
+        .filter(|c| match (&pattern, filter) {
+            (Some(p), _) => p.matches(&c.name),
+            (None, Some(f)) => c.name.contains(f.trim_start_matches('#')),
+            (None, None) => true,
+        })
+        .collect();
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+
+    if json {
+        let list: Vec<serde_json::Value> = channels.iter()
+            .map(|c| serde_json::json!({ "id": c.id, "name": c.name, "private": c.private, "member": c.member }))
+            .collect();
+        println!("{}", serde_json::Value::Array(list));
+        return Ok(());
+    }
+
+    println!("{:<12} {:<8} {:<7} NAME", "ID", "TYPE", "MEMBER");
+    for c in &channels {
+        println!("{:<12} {:<8} {:<7} #{}", c.id, if c.private { "private" } else { "public" },
+            if c.member { "yes" } else { "no" }, c.name);
+    }
+    println!("{} channel(s). The bot must be a member to post in private channels.", channels.len());
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use crate::{BotConfig, check_icon_exists, validate_token};
+
+/// Slack token prefixes: bot, user and (legacy) workspace tokens
+const TOKEN_PREFIXES: &[&str] = &["xoxb-", "xoxp-", "xoxa-"];
+
+/**
+ * Check that a folder exists and files can be created in it.
+ */
+pub fn check_writable(dir: &Path) -> Result<(), String>
+{
+    if !dir.is_dir() {
+        return Err(format!("folder {:?} doesn't exist", dir));
+    }
+    let probe = dir.join(".folder-echo-write-test");
+    std::fs::write(&probe, b"")
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map_err(|e| format!("folder {:?} isn't writable: {}", dir, e))
+}
+
+/**
+ * Check one destination's token and channel.
+ *
+ * @param online Also validate the token and resolve the channel with Slack
+ */
+fn check_destination(conf: &BotConfig, online: bool, problems: &mut Vec<String>)
+{
+    if conf.slack_token.is_empty() {
+        return;     // Webhook-only section
+    }
+    let token = conf.slack_token.get();
+    if !TOKEN_PREFIXES.iter().any(|p| token.starts_with(p)) {
+        problems.push(format!("token for {:?} doesn't look like a Slack token (expected xoxb-...)", conf.slack_channel));
+    }
+    if !online {
+        return;
+    }
+    if let Err(e) = validate_token(conf) {
+        problems.push(format!("token for {:?} failed auth.test: {}", conf.slack_channel, e));
+        return;
+    }
+    let resolved = if conf.slack_channel.starts_with('@') {
+        conf.slack_lookup.user_id_by_name(&conf.slack_channel)
+    } else {
+        conf.slack_lookup.channel_id(&conf.slack_channel)
+    };
+    match resolved {
+        Ok(Some(_)) => {},
+        Ok(None) => problems.push(format!("channel {:?} not found (or not visible to the token)", conf.slack_channel)),
+        Err(e) => problems.push(format!("failed to look up channel {:?}: {}", conf.slack_channel, e)),
+    }
+}
+
+/**
+ * Validate parsed sections without running them, and print a report to stdout.
+ *
+ * @param bots Parsed sections
+ * @param online Also check tokens and channels against Slack
+ * @param json Print the report as JSON: {"ok": ..., "sections": {name: [problems]}}
+ * @return Number of problems found
+ */
+pub fn check_config(bots: &[BotConfig], online: bool, json: bool) -> usize
+{
+    let mut total = 0;
+    let mut report = serde_json::Map::new();
+    for conf in bots {
+        let mut problems = Vec::new();
+        if conf.read_only_source {
+            if !conf.folder.is_dir() {
+                problems.push(format!("folder {:?} doesn't exist", conf.folder));
+            }
+            if conf.posted_dir.exists() {
+                if let Err(e) = check_writable(&conf.posted_dir) {
+                    problems.push(e);
+                }
+            }
+        } else if let Err(e) = check_writable(&conf.folder) {
+            problems.push(e);
+        } else {
+            for dir in [&conf.posted_dir, &conf.rejected_dir] {
+                if dir.exists() {
+                    if let Err(e) = check_writable(dir) {
+                        problems.push(e);
+                    }
+                }
+            }
+        }
+        if let Some(url) = &conf.slack_webhook_url {
+            if !url.starts_with("https://hooks.slack.com/") {
+                problems.push(format!("slack_webhook_url {:?} isn't a Slack incoming webhook URL", url));
+            }
+        }
+        for dest in std::iter::once(conf).chain(conf.extra_destinations.iter()) {
+            check_destination(dest, online, &mut problems);
+        }
+
+        let note = if online { check_icon_exists(conf) } else { None };
+        total += problems.len();
+
+        if json {
+            report.insert(conf.section.clone(), serde_json::json!(problems));
+        } else if problems.is_empty() {
+            println!("[{}] OK", conf.section);
+        } else {
+            println!("[{}] {} problem(s):", conf.section, problems.len());
+            for p in &problems {
+                println!("  - {}", p);
+            }
+        }
+        if let Some(note) = note.filter(|_| !json) {
+            println!("  (note: {})", note);
+        }
+    }
+    if json {
+        println!("{}", serde_json::json!({ "ok": total == 0, "sections": report }));
+    }
+    total
+}
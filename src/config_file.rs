@@ -0,0 +1,168 @@
+use std::path::{Path, PathBuf};
+use log::info;
+
+use crate::{BotError, BotResult};
+
+/// Top-level key listing more config files to merge in (comma-separated globs)
+const INCLUDE_KEY: &str = "include";
+
+/// Config file name for reading the config (INI) from stdin instead
+pub const STDIN: &str = "-";
+
+/// Check if the config is read from stdin (and can't be re-read on reload)
+pub fn is_stdin(path: &Path) -> bool
+{
+    path == Path::new(STDIN)
+}
+
+/**
+ * Load a config file as INI sections, merging in the sections of files
+ * matched by a top-level `include` key, e.g. every `*.ini` in
+ * `/etc/folder-echo/conf.d/` (one level; includes in included files are ignored). A section can
+ * only be defined once across all files.
+ *
+ * @param path Config file path
+ */
+pub fn load(path: &Path) -> BotResult<ini::Ini>
+{
+    let mut ini = load_one(path)?;
+    for file in included_files(&ini)? {
+        info!("Including config file: {:?}", file);
+        let included = load_one(&file)?;
+        for (name, keys) in included.iter() {
+            let name = match name {
+                Some(name) => name,
+                None => continue,
+            };
+            if ini.section(Some(name)).is_some() {
+                return Err(BotError::InvalidConfig(format!("Section {:?} in {:?} is already defined", name, file)));
+            }
+            for (key, val) in keys.iter() {
+                ini.with_section(Some(name)).set(key, val);
+            }
+        }
+    }
+    if let Some(general) = ini.section_mut(None::<String>) {
+        general.remove(INCLUDE_KEY);
+    }
+    Ok(ini)
+}
+
+/**
+ * Include glob patterns of a config file (for watching them).
+ */
+pub fn include_patterns(path: &Path) -> BotResult<Vec<String>>
+{
+    let ini = load_one(path)?;
+    Ok(patterns(&ini))
+}
+
+fn patterns(ini: &ini::Ini) -> Vec<String>
+{
+    ini.section(None::<String>)
+        .and_then(|s| s.get(INCLUDE_KEY))
+        .unwrap_or_default()
+        .split(',')
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/**
+ * Files matched by the include patterns, sorted by name.
+ */
+fn included_files(ini: &ini::Ini) -> BotResult<Vec<PathBuf>>
+{
+    let mut files = Vec::new();
+    for pattern in patterns(ini) {
+        let paths = glob::glob(&pattern).map_err(|e| BotError::InvalidConfig(format!("Invalid include pattern {:?}: {}", pattern, e)))?;
+        files.extend(paths.filter_map(|p| p.ok()).filter(|p| p.is_file()));
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Keys whose values are `<key><separator><value>` lists, which can be written as tables in TOML / YAML
+const TABLE_KEYS: &[(&str, &str)] = &[
+    ("filetype_rules", ":"),
+    ("routes", ":"),
+    ("filetype_icons", "="),
+    ("repost_policy", "="),
+    ("extra_headers", ": "),
+    ("webhook_headers", ": "),
+];
+
+/**
+ * Load a single config file. `.toml`, `.yaml` and `.yml` files
+ * are converted: each top-level table is a section (in file order),
+ * strings / numbers / booleans become values and lists are joined with
+ * ", " (the INI list separator). Nested tables are only allowed for the
+ * `TABLE_KEYS`, and become their INI list syntax. Top-level scalars (like
+ * `include`) go to the unnamed general section. Anything else is INI, as
+ * is stdin (`-`).
+ */
+fn load_one(path: &Path) -> BotResult<ini::Ini>
+{
+    if is_stdin(path) {
+        let mut text = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut text)?;
+        return ini::Ini::load_from_str(&text).map_err(|e| BotError::InvalidConfig(format!("Invalid config on stdin: {}", e)));
+    }
+    let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let doc: serde_json::Value = match ext.as_str() {
+        "toml" => {
+            let text = std::fs::read_to_string(path)?;
+            let value: toml::Value = toml::from_str(&text).map_err(|e| BotError::InvalidConfig(format!("Invalid TOML in {:?}: {}", path, e)))?;
+            serde_json::to_value(value).map_err(|e| BotError::InvalidConfig(format!("Invalid TOML in {:?}: {}", path, e)))?
+        },
+        "yaml" | "yml" => {
+            let text = std::fs::read_to_string(path)?;
+            serde_yaml::from_str(&text).map_err(|e| BotError::InvalidConfig(format!("Invalid YAML in {:?}: {}", path, e)))?
+        },
+        _ => return Ok(ini::Ini::load_from_file(path)?),
+    };
+
+    let sections = doc.as_object().ok_or(BotError::InvalidConfig(format!("Config file {:?} must be a map of sections", path)))?;
+    let mut ini = ini::Ini::new();
+    for (name, section) in sections {
+        if !section.is_object() {
+            ini.with_section(None::<String>).set(name.as_str(), value_string(section, "", name)?);
+            continue;
+        }
+        let keys = section.as_object().ok_or(BotError::InvalidConfig(format!("Config section {:?} must be a map of keys", name)))?;
+        for (key, val) in keys {
+            ini.with_section(Some(name.as_str())).set(key.as_str(), value_string(val, name, key)?);
+        }
+    }
+    Ok(ini)
+}
+
+fn value_string(val: &serde_json::Value, section: &str, key: &str) -> BotResult<String>
+{
+    Ok(match val {
+        serde_json::Value::Object(entries) => {
+            let (_, separator) = TABLE_KEYS.iter().find(|(k, _)| *k == key)
+                .ok_or(BotError::InvalidConfig(format!("{:?}.{} can't be a table", section, key)))?;
+            entries.iter()
+                .map(|(k, v)| scalar(v).map(|v| format!("{}{}{}", k, separator, v))
+                    .ok_or(BotError::InvalidConfig(format!("Unsupported value for {:?}.{}.{}: {}", section, key, k, v))))
+                .collect::<Result<Vec<_>, _>>()?
+                .join(", ")
+        },
+        serde_json::Value::Array(items) => items.iter()
+            .map(|i| scalar(i).ok_or(BotError::InvalidConfig(format!("Unsupported list item in {:?}.{}: {}", section, key, i))))
+            .collect::<Result<Vec<_>, _>>()?
+            .join(", "),
+        v => scalar(v).ok_or(BotError::InvalidConfig(format!("Unsupported value for {:?}.{}: {}", section, key, v)))?,
+    })
+}
+
+fn scalar(v: &serde_json::Value) -> Option<String>
+{
+    match v {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,174 @@
+use std::{collections::HashMap, path::Path, sync::{Arc, Mutex, atomic::{AtomicBool, AtomicU64, Ordering}}};
+use log::{info, warn};
+use anyhow::anyhow;
+
+use crate::BotResult;
+
+const USAGE: &str = "ERR usage: status [json] | debug on|off [<section>] | dry-run on|off <section> | chaos on|off <section>";
+
+/**
+ * Per-section switches that can be flipped at runtime over the control socket,
+ * and counters shown in `status`.
+ */
+#[derive(Debug, Default)]
+pub struct Toggles {
+    pub dry_run: AtomicBool,
+    pub chaos: AtomicBool,      // Fail some posts on purpose (fault injection)
+    pub log_level: Mutex<Option<log::LevelFilter>>,    // Section's configured log_level, restored by `debug off <section>`
+    pub stop: AtomicBool,       // Set to stop the section's worker (config reload)
+    pub watcher_overflows: AtomicU64,   // Times the file watcher dropped events and rescanned
+    pub ready: AtomicBool,      // Watching for files (or the worker has ended)
+    pub heartbeat: AtomicU64,   // Last pass of the bot loop, Unix time (for the systemd watchdog)
+    pub queue_depth: AtomicU64, // Files waiting to be posted (queued or held)
+    pub last_posted: AtomicU64, // Last successful post, Unix time (0 = none yet)
+    pub metrics: crate::metrics::Counters,
+}
+
+impl Toggles {
+    /// Record that the bot loop is alive
+    pub fn beat(&self) {
+        self.heartbeat.store(unix_now(), Ordering::Relaxed);
+    }
+
+    /// Record a successful post
+    pub fn posted(&self) {
+        self.last_posted.store(unix_now(), Ordering::Relaxed);
+    }
+}
+
+/// Current Unix time in seconds
+pub fn unix_now() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Toggles by section name. Sections come and go on config reload.
+pub type SectionToggles = Arc<Mutex<HashMap<String, Arc<Toggles>>>>;
+
+/**
+ * Execute a single control command and return a one-line reply.
+ *
+ * Commands:
+ *   status [json]               - list sections and their toggles (as one line of JSON)
+ *   debug on|off [<section>]    - raise/lower log level for the whole process (but sections with their own log_level),
+ *                                 or for one section (off returns it to its log_level)
+ *   dry-run on|off <section>    - stop/resume posting for a section (files stay in the inbox)
+ *   chaos on|off <section>      - fail some of the section's posts on purpose, to test error handling
+ *
+ * @param line Command line as received from the socket
+ * @param sections Toggles by section name
+ */
+pub fn handle_command(line: &str, sections: &HashMap<String, Arc<Toggles>>) -> String
+{
+    let mut words = line.trim().splitn(3, ' ');
+    let cmd = words.next().unwrap_or("");
+    let arg = words.next().unwrap_or("");
+    let section = words.next().unwrap_or("").trim();
+    let on = match arg {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    };
+
+    match (cmd, on) {
+        ("status", _) if arg == "json" => {
+            let states: serde_json::Map<String, serde_json::Value> = sections.iter()
+                .map(|(n, t)| (n.clone(), serde_json::json!({
+                    "dry_run": t.dry_run.load(Ordering::Relaxed),
+                    "chaos": t.chaos.load(Ordering::Relaxed),
+                    "watcher_overflows": t.watcher_overflows.load(Ordering::Relaxed),
+                })))
+                .collect();
+            serde_json::json!({ "ok": true, "debug": crate::logging::level() >= log::LevelFilter::Debug, "sections": states }).to_string()
+        },
+        ("status", _) => {
+            let mut names: Vec<&String> = sections.keys().collect();
+            names.sort();
+            let states: Vec<String> = names.iter()
+                .map(|n| format!("{:?} dry-run={} chaos={} watcher-overflows={}", n, sections[*n].dry_run.load(Ordering::Relaxed),
+                    sections[*n].chaos.load(Ordering::Relaxed), sections[*n].watcher_overflows.load(Ordering::Relaxed)))
+                .collect();
+            format!("OK debug={} {}", crate::logging::level() >= log::LevelFilter::Debug, states.join(" "))
+        },
+        ("debug", Some(on)) if section.is_empty() => {
+            crate::logging::set_level(if on { log::LevelFilter::Debug } else { log::LevelFilter::Info });
+            info!("Control socket: debug logging {}", arg);
+            format!("OK debug {}", arg)
+        },
+        ("debug", Some(on)) => match sections.get(section) {
+            Some(t) => {
+                crate::logging::set_section_level(section, if on { Some(log::LevelFilter::Debug) } else { *t.log_level.lock().unwrap() });
+                info!("Control socket: debug logging {} for {:?}", arg, section);
+                format!("OK debug {} {}", arg, section)
+            },
+            None => format!("ERR unknown section {:?}", section),
+        },
+        ("dry-run", Some(on)) => match sections.get(section) {
+            Some(t) => {
+                t.dry_run.store(on, Ordering::Relaxed);
+                info!("Control socket: dry-run {} for {:?}", arg, section);
+                format!("OK dry-run {} {}", arg, section)
+            },
+            None => format!("ERR unknown section {:?}", section),
+        },
+        ("chaos", Some(on)) => match sections.get(section) {
+            Some(t) => {
+                t.chaos.store(on, Ordering::Relaxed);
+                warn!("Control socket: chaos mode {} for {:?}", arg, section);
+                format!("OK chaos {} {}", arg, section)
+            },
+            None => format!("ERR unknown section {:?}", section),
+        },
+        _ => USAGE.to_string(),
+    }
+}
+
+/**
+ * Listen for line-based commands on a Unix socket. Blocks forever.
+ * A stale socket file from a previous run is replaced.
+ *
+ * @param path Socket path
+ * @param sections Toggles by section name
+ */
+#[cfg(unix)]
+pub fn serve(path: &Path, sections: SectionToggles) -> BotResult<()>
+{
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            return Err(anyhow!("Control socket path exists and is not a socket: {:?}", path).into());
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    info!("Listening for control commands on {:?}", path);
+
+    fn handle_client(stream: UnixStream, sections: &SectionToggles) -> std::io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let reply = handle_command(&line?, &sections.lock().unwrap());
+            writeln!(writer, "{}", reply)?;
+        }
+        Ok(())
+    }
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = handle_client(stream, &sections) {
+                    warn!("Control socket client error: {:?}", e);
+                }
+            },
+            Err(e) => warn!("Control socket accept failed: {:?}", e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn serve(_path: &Path, _sections: SectionToggles) -> BotResult<()>
+{
+    Err(anyhow!("Control socket is only supported on Unix-like systems").into())
+}
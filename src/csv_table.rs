@@ -0,0 +1,63 @@
+use std::path::Path;
+use anyhow::anyhow;
+
+use crate::BotResult;
+
+/// Slack rejects message texts longer than 40k characters; leave room for title and comment
+const MAX_TABLE_CHARS: usize = 35_000;
+
+/**
+ * Render a CSV file as an aligned plain text table inside a code block,
+ * first row as header.
+ *
+ * @param path Path to CSV file
+ * @param max_rows Maximum number of rows (including header) to render
+ * @return Table as Slack markup, or None if the file is too big to render
+ */
+pub fn render_csv_table(path: &Path, max_rows: usize) -> BotResult<Option<String>>
+{
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| anyhow!("Failed to read CSV: {}", e))?;
+
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    for rec in reader.records() {
+        let rec = rec.map_err(|e| anyhow!("Failed to parse CSV: {}", e))?;
+        if rows.len() >= max_rows {
+            return Ok(None);
+        }
+        rows.push(rec.iter().map(|f| f.replace('`', "'")).collect());
+    }
+    if rows.is_empty() {
+        return Ok(None);
+    }
+
+    let n_cols = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..n_cols)
+        .map(|c| rows.iter().map(|r| r.get(c).map(|f| f.chars().count()).unwrap_or(0)).max().unwrap_or(0))
+        .collect();
+
+    let format_row = |row: &Vec<String>| -> String {
+        widths.iter().enumerate()
+            .map(|(c, w)| {
+                let field = row.get(c).map(|f| f.as_str()).unwrap_or("");
+                format!("{}{}", field, " ".repeat(w - field.chars().count()))
+            })
+            .collect::<Vec<_>>()
+            .join(" | ")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut lines = vec![format_row(&rows[0])];
+    lines.push(widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("-+-"));
+    lines.extend(rows[1..].iter().map(format_row));
+
+    let table = format!("```\n{}\n```", lines.join("\n"));
+    if table.chars().count() > MAX_TABLE_CHARS {
+        return Ok(None);
+    }
+    Ok(Some(table))
+}
@@ -0,0 +1,42 @@
+use std::path::Path;
+use anyhow::anyhow;
+
+use crate::BotResult;
+
+/**
+ * Fork to the background for classic init systems (`--daemon`), detaching
+ * from the terminal. Call before starting any threads.
+ *
+ * The working directory stays the same, so relative paths in the config
+ * keep working. Stdout and stderr are discarded, so log to a file
+ * (`logging::init_file()`) or syslog, set up before this.
+ *
+ * @param pidfile Write the daemon's process ID here (locked while it runs)
+ */
+#[cfg(unix)]
+pub fn daemonize(pidfile: Option<&Path>) -> BotResult<()>
+{
+    let mut daemon = daemonize::Daemonize::new()
+        .working_directory(std::env::current_dir()?);
+    if let Some(pidfile) = pidfile {
+        daemon = daemon.pid_file(pidfile);
+    }
+    daemon.start().map_err(|e| anyhow!("Failed to start as a daemon: {}", e))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn daemonize(_pidfile: Option<&Path>) -> BotResult<()>
+{
+    Err(anyhow!("--daemon is only supported on Unix-like systems").into())
+}
+
+/**
+ * Write the process ID to `pidfile` when running in the foreground.
+ */
+pub fn write_pidfile(pidfile: &Path) -> BotResult<()>
+{
+    std::fs::write(pidfile, format!("{}\n", std::process::id()))
+        .map_err(|e| anyhow!("Failed to write --pidfile {:?}: {}", pidfile, e))?;
+    Ok(())
+}
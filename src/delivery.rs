@@ -0,0 +1,85 @@
+use std::path::{Path, PathBuf};
+use sha2::{Digest, Sha256};
+use anyhow::anyhow;
+
+use crate::{BotConfig, BotResult};
+
+/**
+ * Stable name for a destination in delivery state files (and the repost
+ * log): workspace ID and channel for Slack, so that it stays the same when
+ * the token is rotated. The workspace ID is looked up when the section
+ * starts; if that failed, the legacy label is used, so posting never
+ * waits for (or fails on) a lookup.
+ */
+pub fn label(conf: &BotConfig) -> String
+{
+    match conf.slack_lookup.cached_team_id() {
+        Some(team) if !conf.slack_token.is_empty() => format!("{}:{}", team, conf.slack_channel),
+        _ => legacy_label(conf),        // Webhook, Discord, email... or workspace not known yet
+    }
+}
+
+/**
+ * Label by a short hash of the token, as written by earlier versions
+ * (and still used for destinations without a token).
+ */
+pub fn legacy_label(conf: &BotConfig) -> String
+{
+    let hash = format!("{:x}", Sha256::digest(conf.slack_token.get().as_bytes()));
+    format!("{}:{}", &hash[..8], conf.slack_channel)
+}
+
+/**
+ * Which destinations have already received a file, so that a file that
+ * failed for some destinations can be retried without double-posting
+ * to the others. State is tied to the file's size and mtime.
+ *
+ * State file contents: "<size> <mtime>\n<label>\n<label>..."
+ */
+pub struct DeliveryState {
+    path: PathBuf,
+    key: String,
+}
+
+impl DeliveryState {
+    pub fn new(state_dir: &Path, file: &Path) -> BotResult<Self>
+    {
+        let name = file.file_name().ok_or(anyhow!("Invalid file path"))?;
+        let meta = std::fs::metadata(file)?;
+        let mut state_name = name.to_os_string();
+        state_name.push(".delivered");
+        Ok(DeliveryState {
+            path: state_dir.join(state_name),
+            key: format!("{} {:?}", meta.len(), meta.modified()?),
+        })
+    }
+
+    /**
+     * Labels of destinations that already got this version of the file.
+     */
+    pub fn delivered(&self) -> Vec<String>
+    {
+        let content = std::fs::read_to_string(&self.path).unwrap_or_default();
+        let mut lines = content.lines();
+        if lines.next() != Some(self.key.as_str()) {
+            return vec![];
+        }
+        lines.map(|l| l.to_string()).collect()
+    }
+
+    pub fn mark_delivered(&self, label: &str) -> BotResult<()>
+    {
+        let mut done = self.delivered();
+        done.push(label.to_string());
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, format!("{}\n{}\n", self.key, done.join("\n")))?;
+        Ok(())
+    }
+
+    pub fn clear(&self)
+    {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,202 @@
+use log::{info, error};
+use anyhow::anyhow;
+
+use crate::{BotConfig, BotError, BotResult, BotSlackMessage, check_slack_response, external_upload, icon_param, slack_client};
+
+/**
+ * Chat service that a section posts to. The watcher, queue and settle logic
+ * only go through this (via `post_message()`), so other chat backends can be
+ * added by implementing it.
+ */
+pub trait Destination: std::fmt::Debug + Send + Sync {
+    /**
+     * Post a text message: `msg.title` and `msg.text`, in `msg.channel`
+     * (or the section's channel) and `msg.thread_ts`, if set.
+     *
+     * @return Response; `channel` and `ts` are used for threading, if the backend has them
+     */
+    fn post_text(&self, conf: &BotConfig, msg: &BotSlackMessage) -> BotResult<serde_json::Value>;
+
+    /**
+     * Upload `msg.file` (or its contents as a snippet, if `msg.snippet_type`
+     * is set) with `msg.title` and `msg.text` as the comment.
+     *
+     * @return Response; `file` is used for the upload log and threading, if the backend has them
+     */
+    fn post_file(&self, conf: &BotConfig, msg: &BotSlackMessage) -> BotResult<serde_json::Value>;
+
+    /**
+     * Stable name for logs and delivery state when the destination is one of
+     * several in a section (e.g. "discord:<channel id>"). Must not contain secrets.
+     */
+    fn label(&self) -> String;
+}
+
+/**
+ * Slack Web API (`slack_token`), with messages going through the section's
+ * incoming webhook instead if it has `slack_webhook_url`.
+ */
+#[derive(Debug)]
+pub struct Slack;
+
+impl Destination for Slack {
+    fn label(&self) -> String {
+        "slack".to_string()     // Slack destinations are labeled by workspace and channel, see `delivery::label()`
+    }
+
+    fn post_text(&self, conf: &BotConfig, msg: &BotSlackMessage) -> BotResult<serde_json::Value> {
+        if let Some(url) = &conf.slack_webhook_url {
+            return post_webhook(conf, url, msg);
+        }
+        info!("Posting message to Slack: {:?}", &msg);
+
+        let mut params = std::collections::HashMap::new();
+        params.insert("channel", msg.channel.clone().unwrap_or_else(|| conf.slack_channel.clone()));
+        params.insert("username", conf.bot_name.clone());
+        if let Some(ts) = &msg.thread_ts {
+            params.insert("thread_ts", ts.clone());
+        }
+        if let Some(text) = &msg.text {
+            let mut text = text.clone();
+            if let Some(title) = &msg.title {
+                text = format!("*{}*\n{}", title, text);
+            }
+            params.insert("text", text);
+        }
+        if let Some(icon) = msg.icon.as_ref().or(conf.bot_icon.as_ref()) {
+            let (key, val) = icon_param(icon);
+            params.insert(key, val);
+        }
+        if let Some(blocks) = &msg.blocks {
+            params.insert("blocks", blocks.to_string());
+        }
+        if let Some(unfurl) = msg.unfurl {
+            params.insert("unfurl_links", unfurl.to_string());
+            params.insert("unfurl_media", unfurl.to_string());
+        }
+
+        let _permit = conf.http_semaphore.acquire();
+        let res = slack_client(&conf.extra_headers).build()?
+            .post("https://slack.com/api/chat.postMessage")
+            .form(&params)
+            .bearer_auth(conf.slack_token.get())
+            .send()?;
+        check_response(conf, res)
+    }
+
+    fn post_file(&self, conf: &BotConfig, msg: &BotSlackMessage) -> BotResult<serde_json::Value> {
+        let file = msg.file.as_ref().ok_or(anyhow!("No file to upload"))?;
+        if conf.slack_token.is_empty() {
+            return Err(BotError::InvalidConfig("Can't upload files without slack_token (section only has slack_webhook_url)".to_string()));
+        }
+        if conf.external_upload {
+            info!("Uploading file to Slack (external upload): {:?}", &msg);
+            return external_upload::upload(conf, msg, &conf.posted_dir.join(".upload_state"));
+        }
+        info!("Posting file to Slack: {:?}", &msg);
+
+        let mut form = reqwest::blocking::multipart::Form::new();
+        if let Some(text) = &msg.text {
+            form = form.text("initial_comment", text.clone());
+        }
+        if let Some(title) = &msg.title {
+            form = form.text("title", title.clone());
+        }
+        form = form.text("username", conf.bot_name.clone());
+        form = form.text("channels", msg.channel.clone().unwrap_or_else(|| conf.slack_channel.clone()));
+        if let Some(ts) = &msg.thread_ts {
+            form = form.text("thread_ts", ts.clone());
+        }
+
+        if let Some(filetype) = &msg.snippet_type {
+            let filename = file.file_name().ok_or(anyhow!("Invalid file path"))?.to_string_lossy().to_string();
+            form = form.text("content", std::fs::read_to_string(file)?);
+            form = form.text("filetype", filetype.clone());
+            form = form.text("filename", filename);
+        } else {
+            let part = reqwest::blocking::multipart::Part::file(file)?;
+            form = form.part("file", part);
+        }
+
+        // A hung connection would otherwise block the section's worker forever.
+        // Timeouts are transient errors, so the upload is retried.
+        let _permit = conf.http_semaphore.acquire();
+        let client = slack_client(&conf.extra_headers)
+            .timeout(conf.upload_timeout)
+            .build()?;
+        let res = client.post("https://slack.com/api/files.upload")
+            .multipart(form)
+            .bearer_auth(conf.slack_token.get())
+            .send()?;
+        check_response(conf, res)
+    }
+}
+
+/**
+ * Check a Web API response, dropping cached channel / user IDs on errors
+ * that mean they're stale.
+ */
+fn check_response(conf: &BotConfig, res: reqwest::blocking::Response) -> BotResult<serde_json::Value> {
+    let json = match check_slack_response(res) {
+        Ok(json) => json,
+        Err(e) => {
+            conf.slack_lookup.invalidate_on_error(&e);
+            return Err(e);
+        },
+    };
+    info!("Got Ok from Slack");
+    Ok(json)
+}
+
+/**
+ * Post a text message through an incoming webhook. The webhook decides the
+ * channel, so `msg.channel` is ignored; `username` and icon overrides only
+ * work with legacy webhooks, newer ones silently use the app's own.
+ *
+ * @param conf Bot configuration
+ * @param url Incoming webhook URL
+ * @param msg Message to post (without file)
+ * @return `{"ok": true}` on success (webhooks don't return JSON)
+ */
+fn post_webhook(conf: &BotConfig, url: &str, msg: &BotSlackMessage) -> BotResult<serde_json::Value> {
+    info!("Posting message to Slack webhook: {:?}", &msg);
+    let text = match (&msg.title, &msg.text) {
+        (Some(title), Some(text)) => format!("*{}*\n{}", title, text),
+        (Some(title), None) => format!("*{}*", title),
+        (None, text) => text.clone().unwrap_or_default(),
+    };
+    let mut payload = serde_json::json!({ "text": text, "username": conf.bot_name });
+    if let Some(ts) = &msg.thread_ts {
+        payload["thread_ts"] = ts.clone().into();
+    }
+    if let Some(icon) = msg.icon.as_ref().or(conf.bot_icon.as_ref()) {
+        let (key, val) = icon_param(icon);
+        payload[key] = val.into();
+    }
+    if let Some(blocks) = &msg.blocks {
+        payload["blocks"] = blocks.clone();
+    }
+    if let Some(unfurl) = msg.unfurl {
+        payload["unfurl_links"] = unfurl.into();
+        payload["unfurl_media"] = unfurl.into();
+    }
+
+    let _permit = conf.http_semaphore.acquire();
+    let res = slack_client(&conf.extra_headers).build()?
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(payload.to_string())
+        .send()?;
+    // Rate limits and server errors as HTTP errors (retried), others carry the reason as plain text
+    let status = res.status();
+    if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(res.error_for_status().unwrap_err().into());
+    }
+    let body = res.text().unwrap_or_default();
+    if !status.is_success() {
+        error!("Slack webhook error response: {} {}", status, body);
+        return Err(BotError::SlackApiError(body.trim().to_string()));
+    }
+    info!("Got Ok from Slack webhook");
+    Ok(serde_json::json!({ "ok": true }))
+}
@@ -0,0 +1,161 @@
+use log::{info, debug, error};
+use anyhow::anyhow;
+use sha2::{Digest, Sha256};
+
+use crate::{BotConfig, BotError, BotResult, BotSlackMessage, destination::Destination, secrets};
+
+const API_URL: &str = "https://discord.com/api/v10";
+/// Discord's limit for message content
+const CONTENT_MAX_CHARS: usize = 2000;
+
+/**
+ * Discord channel, through an incoming webhook or as a bot
+ * (`discord_webhook_url`, or `discord_token` + `discord_channel_id`).
+ */
+pub enum Discord {
+    Webhook { url: String },
+    Bot { token: String, channel_id: String },
+}
+
+impl std::fmt::Debug for Discord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Discord::Webhook { .. } => write!(f, "Discord(webhook, <redacted>)"),
+            Discord::Bot { channel_id, .. } => write!(f, "Discord(bot, channel {}, <redacted>)", channel_id),
+        }
+    }
+}
+
+impl Discord {
+    /**
+     * Discord destination from a config section.
+     *
+     * @return None if the section has no Discord keys
+     */
+    pub fn from_config(section: &ini::Properties) -> BotResult<Option<Self>> {
+        let get = |key: &str| section.get(key).map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        match (get("discord_webhook_url"), get("discord_token"), get("discord_channel_id")) {
+            (None, None, None) => Ok(None),
+            (Some(url), None, None) => {
+                // The URL has the webhook's token in it, so it can come from a secret too
+                let url = secrets::interpolate(&url)?.0;
+                if !url.starts_with("https://") {
+                    return Err(BotError::InvalidConfig("discord_webhook_url isn't an https URL".to_string()));
+                }
+                Ok(Some(Discord::Webhook { url }))
+            },
+            (None, Some(token), Some(channel_id)) => Ok(Some(Discord::Bot {
+                token: secrets::interpolate(&token)?.0,
+                channel_id,
+            })),
+            _ => Err(BotError::InvalidConfig("Discord needs either discord_webhook_url, or discord_token and discord_channel_id".to_string())),
+        }
+    }
+
+    fn post(&self, conf: &BotConfig, msg: &BotSlackMessage) -> BotResult<serde_json::Value> {
+        if msg.thread_ts.is_some() {
+            debug!("Discord has no Slack-style threads, posting in the channel: {:?}", msg);
+        }
+        let mut payload = serde_json::json!({ "content": content(msg) });
+        if let Discord::Webhook { .. } = self {
+            payload["username"] = conf.bot_name.clone().into();
+            if let Some(icon) = msg.icon.as_ref().or(conf.bot_icon.as_ref()).filter(|i| i.starts_with("https://") || i.starts_with("http://")) {
+                payload["avatar_url"] = icon.clone().into();
+            }
+        }
+        if msg.unfurl == Some(false) {
+            payload["flags"] = 4.into();       // SUPPRESS_EMBEDS
+        }
+
+        let mut client = reqwest::blocking::Client::builder();
+        if msg.file.is_some() {
+            client = client.timeout(conf.upload_timeout);
+        }
+        let req = match self {
+            Discord::Webhook { url } => client.build()?.post(url).query(&[("wait", "true")]),
+            Discord::Bot { token, channel_id } => client.build()?
+                .post(format!("{}/channels/{}/messages", API_URL, channel_id))
+                .header(reqwest::header::AUTHORIZATION, format!("Bot {}", token)),
+        };
+        let req = match &msg.file {
+            Some(file) => {
+                let form = reqwest::blocking::multipart::Form::new()
+                    .text("payload_json", payload.to_string())
+                    .part("files[0]", reqwest::blocking::multipart::Part::file(file)?);
+                req.multipart(form)
+            },
+            None => req
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(payload.to_string()),
+        };
+
+        let _permit = conf.http_semaphore.acquire();
+        let json = check_response(req.send()?)?;
+        info!("Got Ok from Discord");
+        let attachment = &json["attachments"][0];
+        Ok(match attachment["id"].as_str() {
+            Some(id) => serde_json::json!({ "ok": true, "message": json, "file": { "id": id, "permalink": attachment["url"] } }),
+            None => serde_json::json!({ "ok": true, "message": json }),
+        })
+    }
+}
+
+impl Destination for Discord {
+    fn label(&self) -> String {
+        match self {
+            Discord::Webhook { url } => {
+                let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+                format!("discord:{}", &hash[..8])
+            },
+            Discord::Bot { channel_id, .. } => format!("discord:{}", channel_id),
+        }
+    }
+
+    fn post_text(&self, conf: &BotConfig, msg: &BotSlackMessage) -> BotResult<serde_json::Value> {
+        info!("Posting message to Discord: {:?}", &msg);
+        self.post(conf, msg)
+    }
+
+    fn post_file(&self, conf: &BotConfig, msg: &BotSlackMessage) -> BotResult<serde_json::Value> {
+        msg.file.as_ref().ok_or(anyhow!("No file to upload"))?;
+        info!("Posting file to Discord: {:?}", &msg);
+        self.post(conf, msg)
+    }
+}
+
+/// Message content: bold title and text (or the text of Block Kit sections), cut to Discord's limit
+fn content(msg: &BotSlackMessage) -> String {
+    let blocks_text = msg.blocks.as_ref().and_then(|b| b.as_array()).map(|blocks| blocks.iter()
+        .filter_map(|b| b["text"]["text"].as_str().or_else(|| b["elements"][0]["text"].as_str()))
+        .collect::<Vec<_>>()
+        .join("\n"));
+    let text = blocks_text.or_else(|| msg.text.clone());
+    let content = match (&msg.title, &text) {
+        (Some(title), Some(text)) => format!("**{}**\n{}", title, text),
+        (Some(title), None) => format!("**{}**", title),
+        (None, text) => text.clone().unwrap_or_default(),
+    };
+    if content.chars().count() <= CONTENT_MAX_CHARS {
+        return content;
+    }
+    content.chars().take(CONTENT_MAX_CHARS - 1).chain(std::iter::once('…')).collect()
+}
+
+/**
+ * Check a Discord API response. Rate limits and server errors are returned
+ * as HTTP errors (retried), others with Discord's error message.
+ */
+fn check_response(res: reqwest::blocking::Response) -> BotResult<serde_json::Value> {
+    let status = res.status();
+    if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(res.error_for_status().unwrap_err().into());
+    }
+    let body = res.text().unwrap_or_default();
+    let json = serde_json::from_str::<serde_json::Value>(&body).unwrap_or_default();
+    if !status.is_success() {
+        error!("Discord error response: {} {}", status, body);
+        let message = json["message"].as_str().map(|s| s.to_string()).unwrap_or_else(|| body.trim().to_string());
+        return Err(BotError::DestinationError(format!("Discord: {} ({})", message, status)));
+    }
+    Ok(json)
+}
@@ -0,0 +1,114 @@
+use log::{info, error};
+use anyhow::anyhow;
+use lettre::{Message, SmtpTransport, Transport, message::{Attachment, Mailbox, MultiPart, SinglePart, header::ContentType}};
+
+use crate::{BotConfig, BotError, BotResult, BotSlackMessage, destination::Destination, parse_size, secrets};
+
+/// Default `smtp_max_size`, below the limits of most mail servers (after base64)
+const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/**
+ * Email recipients, each file sent as an attachment through an SMTP
+ * server (`smtp_url`, `smtp_from`, `smtp_to`).
+ */
+pub struct Email {
+    transport: SmtpTransport,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    max_size: u64,      // Largest file to attach
+}
+
+impl std::fmt::Debug for Email {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to: Vec<String> = self.to.iter().map(|m| m.to_string()).collect();
+        write!(f, "Email(from {}, to {}, <redacted>)", self.from, to.join(", "))
+    }
+}
+
+impl Email {
+    /**
+     * Email destination from a config section.
+     *
+     * @return None if the section has no SMTP keys
+     */
+    pub fn from_config(section: &ini::Properties) -> BotResult<Option<Self>> {
+        let get = |key: &str| section.get(key).map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let (url, from, to) = match (get("smtp_url"), get("smtp_from"), get("smtp_to")) {
+            (None, None, None) => return Ok(None),
+            (Some(url), Some(from), Some(to)) => (url, from, to),
+            _ => return Err(BotError::InvalidConfig("Email needs smtp_url, smtp_from and smtp_to".to_string())),
+        };
+        let transport = SmtpTransport::from_url(&secrets::interpolate(&url)?.0)
+            .map_err(|e| BotError::InvalidConfig(format!("Invalid smtp_url: {}", e)))?
+            .build();
+        let mailbox = |key: &str, s: &str| s.parse::<Mailbox>()
+            .map_err(|e| BotError::InvalidConfig(format!("Invalid {} address {:?}: {}", key, s, e)));
+        let from = mailbox("smtp_from", &from)?;
+        let to = to.split(',').map(|s| s.trim()).filter(|s| !s.is_empty())
+            .map(|s| mailbox("smtp_to", s))
+            .collect::<BotResult<Vec<_>>>()?;
+        let max_size = match get("smtp_max_size") {
+            Some(s) => parse_size(&s).ok_or(BotError::InvalidConfig(format!("Invalid smtp_max_size (expected e.g. 10MB): {:?}", s)))?,
+            None => DEFAULT_MAX_SIZE,
+        };
+        Ok(Some(Email { transport, from, to, max_size }))
+    }
+
+    fn send(&self, conf: &BotConfig, msg: &BotSlackMessage, attachment: Option<SinglePart>) -> BotResult<serde_json::Value> {
+        let subject = msg.title.clone().unwrap_or_else(|| format!("Message from {}", conf.bot_name));
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(subject);
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        let body = SinglePart::plain(msg.text.clone().unwrap_or_default());
+        let email = match attachment {
+            Some(attachment) => builder.multipart(MultiPart::mixed().singlepart(body).singlepart(attachment)),
+            None => builder.singlepart(body),
+        }.map_err(|e| anyhow!("Failed to build email: {}", e))?;
+
+        let _permit = conf.http_semaphore.acquire();
+        match self.transport.send(&email) {
+            Ok(res) => {
+                info!("Got Ok from SMTP server: {:?}", res.code());
+                Ok(serde_json::json!({ "ok": true }))
+            },
+            // 4xx replies, timeouts and connection / TLS failures are worth retrying, 5xx replies aren't
+            Err(e) if e.is_transient() || e.is_timeout() || !(e.is_permanent() || e.is_client() || e.is_response()) => {
+                error!("SMTP error (temporary): {:?}", e);
+                Err(BotError::DestinationUnavailable(format!("Email: {}", e)))
+            },
+            Err(e) => {
+                error!("SMTP error: {:?}", e);
+                Err(BotError::DestinationError(format!("Email: {}", e)))
+            },
+        }
+    }
+}
+
+impl Destination for Email {
+    fn label(&self) -> String {
+        let to: Vec<String> = self.to.iter().map(|m| m.email.to_string()).collect();
+        format!("email:{}", to.join(","))
+    }
+
+    fn post_text(&self, conf: &BotConfig, msg: &BotSlackMessage) -> BotResult<serde_json::Value> {
+        info!("Sending message by email: {:?}", &msg);
+        self.send(conf, msg, None)
+    }
+
+    fn post_file(&self, conf: &BotConfig, msg: &BotSlackMessage) -> BotResult<serde_json::Value> {
+        let file = msg.file.as_ref().ok_or(anyhow!("No file to upload"))?;
+        let size = std::fs::metadata(file)?.len();
+        if size > self.max_size {
+            return Err(BotError::FileTooLarge(format!("{} bytes, over smtp_max_size ({} bytes)", size, self.max_size)));
+        }
+        info!("Sending file by email: {:?}", &msg);
+        let name = file.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let content_type = ContentType::parse(mime_guess::from_path(file).first_or_octet_stream().as_ref())
+            .map_err(|e| anyhow!("Invalid content type for {:?}: {}", file, e))?;
+        let attachment = Attachment::new(name).body(std::fs::read(file)?, content_type);
+        self.send(conf, msg, Some(attachment))
+    }
+}
@@ -0,0 +1,115 @@
+use std::{sync::Mutex, time::{Duration, SystemTime}};
+use log::{info, warn, error};
+
+use crate::{BotConfig, BotError, BotSlackMessage, post_message_with_retries, resolve_mention};
+
+#[derive(Debug, Default)]
+struct State {
+    failing_since: Option<SystemTime>,      // First failed delivery since the last successful one
+    escalated: bool,
+}
+
+/**
+ * Escalation of ongoing delivery failures: when a section has failed to
+ * deliver files for longer than `escalate_after` (with no successful
+ * delivery in between), mention `escalate_mention` in `escalate_channel`
+ * and call `escalate_webhook_url` with severity=critical, once. A later
+ * successful delivery posts a recovery notice.
+ */
+#[derive(Debug)]
+pub struct Escalation {
+    after: Duration,
+    mention: Vec<String>,
+    channel: Option<String>,        // Default: the section's own channel
+    webhook_url: Option<String>,
+    state: Mutex<State>,
+}
+
+impl Escalation {
+    pub fn new(after: Duration, mention: Vec<String>, channel: Option<String>, webhook_url: Option<String>) -> Self {
+        Escalation { after, mention, channel, webhook_url, state: Mutex::new(State::default()) }
+    }
+
+    /**
+     * Count a failed delivery, escalating if failures have gone on for too long.
+     * Files refused for their own sake (too large, type not allowed) don't count.
+     */
+    pub fn failure(&self, conf: &BotConfig, err: &BotError) {
+        if matches!(err, BotError::FileTooLarge(_) | BotError::FileTypeNotAllowed(_)) {
+            return;
+        }
+        let now = SystemTime::now();
+        let since = {
+            let mut state = self.state.lock().unwrap();
+            let since = *state.failing_since.get_or_insert(now);
+            if state.escalated || now.duration_since(since).unwrap_or_default() < self.after {
+                return;
+            }
+            state.escalated = true;
+            since
+        };
+        let duration = humantime(now.duration_since(since).unwrap_or_default());
+        warn!("Deliveries have been failing for {}, escalating", duration);
+        let mentions: Vec<String> = self.mention.iter().map(|m| resolve_mention(conf, m)).collect();
+        let text = format!("{} Files from '{}' have failed to post for {}. Latest error [{}]: {}",
+            mentions.join(" "), conf.section, duration, err.code(), err);
+        self.notify(conf, ":rotating_light:", "critical", text.trim_start(), since, Some(err));
+    }
+
+    /**
+     * Count a successful delivery, posting a recovery notice if failures were escalated.
+     */
+    pub fn success(&self, conf: &BotConfig) {
+        let (since, escalated) = {
+            let mut state = self.state.lock().unwrap();
+            let since = state.failing_since.take();
+            (since, std::mem::take(&mut state.escalated))
+        };
+        if let (Some(since), true) = (since, escalated) {
+            info!("Deliveries work again, posting recovery notice");
+            let text = format!("Files from '{}' are posting again.", conf.section);
+            self.notify(conf, ":white_check_mark:", "resolved", &text, since, None);
+        }
+    }
+
+    fn notify(&self, conf: &BotConfig, icon: &str, severity: &str, text: &str, since: SystemTime, err: Option<&BotError>) {
+        let res = post_message_with_retries(conf, &BotSlackMessage {
+            text: Some(text.to_string()),
+            icon: Some(icon.to_string()),
+            channel: self.channel.clone(),
+            ..Default::default()
+        });
+        if let Err(e) = res {
+            error!("Failed to post escalation notice: {:?}", e);
+        }
+        if let Some(url) = &self.webhook_url {
+            let payload = serde_json::json!({
+                "severity": severity,
+                "section": conf.section,
+                "text": text,
+                "failing_since": chrono::DateTime::<chrono::Utc>::from(since).to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                "code": err.map(|e| e.code()),
+                "error": err.map(|e| e.to_string()),
+            });
+            let res = reqwest::blocking::Client::builder().timeout(Duration::from_secs(30)).build()
+                .and_then(|c| c.post(url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(payload.to_string())
+                    .send())
+                .and_then(|r| r.error_for_status());
+            if let Err(e) = res {
+                error!("Failed to call escalate_webhook_url: {:?}", e);
+            }
+        }
+    }
+}
+
+/// "2h 5m" style duration, to the minute
+fn humantime(d: Duration) -> String {
+    let mins = d.as_secs() / 60;
+    match (mins / 60, mins % 60) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h {}m", h, m),
+    }
+}
@@ -0,0 +1,202 @@
+use std::path::{Path, PathBuf};
+use log::{info, warn};
+use anyhow::anyhow;
+
+use crate::{BotConfig, BotError, BotResult, BotSlackMessage, check_slack_response, slack_client};
+
+/**
+ * Upload a file with Slack's external upload flow:
+ * files.getUploadURLExternal -> POST bytes -> files.completeUploadExternal.
+ *
+ * Slack's upload URL takes the whole file in one request (no ranges), so
+ * an interrupted transfer starts over. What *is* persisted is the ID of a
+ * completely transferred file, so that if the completion call fails (or
+ * the process dies before it), the next attempt only completes the upload
+ * instead of sending the bytes again.
+ *
+ * @param conf Bot configuration
+ * @param msg Message with `file` set
+ * @param state_dir Where to keep in-progress upload state
+ * @return Slack response, with the uploaded file also under "file" (like files.upload)
+ */
+pub fn upload(conf: &BotConfig, msg: &BotSlackMessage, state_dir: &Path) -> BotResult<serde_json::Value>
+{
+    let file = msg.file.as_ref().ok_or(anyhow!("No file to upload"))?;
+    let target = Target {
+        channel: msg.channel.as_deref().unwrap_or(&conf.slack_channel),
+        thread_ts: msg.thread_ts.as_deref(),
+        text: msg.text.as_deref(),
+    };
+    upload_many(conf, &[(file.clone(), msg.title.clone())], &target, msg.snippet_type.as_deref(), state_dir)
+}
+
+/**
+ * Where and how to share uploaded files.
+ */
+pub struct Target<'a> {
+    pub channel: &'a str,
+    pub thread_ts: Option<&'a str>,
+    pub text: Option<&'a str>,      // Initial comment
+}
+
+/**
+ * Upload several files and share them as a single message.
+ *
+ * @param conf Bot configuration
+ * @param files (path, title) pairs
+ * @param target Channel, thread and comment for the message
+ * @param snippet_type Slack snippet type, for single text file uploads
+ * @param state_dir Where to keep in-progress upload state
+ * @return Slack response, first uploaded file also under "file"
+ */
+pub fn upload_many(conf: &BotConfig, files: &[(PathBuf, Option<String>)], target: &Target, snippet_type: Option<&str>, state_dir: &Path)
+    -> BotResult<serde_json::Value>
+{
+    let mut ids = Vec::new();
+    let mut resumed = false;
+    for (path, title) in files {
+        let (file_id, was_resumed) = transfer(conf, path, snippet_type, state_dir)?;
+        resumed |= was_resumed;
+        ids.push((file_id, title.clone()));
+    }
+
+    let res = match complete(conf, &ids, target) {
+        // Saved file IDs may have expired on Slack's side; start over once
+        Err(BotError::SlackApiError(e)) if resumed => {
+            warn!("Could not complete earlier upload ({}), uploading again", e);
+            clear_state(files, state_dir);
+            let mut ids = Vec::new();
+            for (path, title) in files {
+                ids.push((transfer(conf, path, snippet_type, state_dir)?.0, title.clone()));
+            }
+            complete(conf, &ids, target)
+        },
+        res => res,
+    };
+    if res.is_ok() {
+        clear_state(files, state_dir);
+    }
+    res
+}
+
+/**
+ * Transfer the bytes of one file to Slack, or reuse an earlier completed
+ * transfer of the same file (same size and mtime).
+ *
+ * @return (Slack file ID, true if reused from saved state)
+ */
+fn transfer(conf: &BotConfig, file: &Path, snippet_type: Option<&str>, state_dir: &Path) -> BotResult<(String, bool)>
+{
+    let state_file = state_path(state_dir, file)?;
+    let meta = std::fs::metadata(file)?;
+    let state_key = format!("{} {:?}", meta.len(), meta.modified()?);
+
+    if let Some(file_id) = read_state(&state_file, &state_key) {
+        info!("Resuming upload of {:?} (Slack file {}), skipping transfer", file, file_id);
+        return Ok((file_id, true));
+    }
+
+    // Get upload URL
+    let filename = file.file_name().ok_or(anyhow!("Invalid file path"))?.to_string_lossy().to_string();
+    let length = meta.len().to_string();
+    let mut params = vec![("filename", filename.as_str()), ("length", length.as_str())];
+    if let Some(t) = snippet_type {
+        params.push(("snippet_type", t));
+    }
+    let json = {
+        let _permit = conf.http_semaphore.acquire();
+        let res = slack_client(&conf.extra_headers).build()?
+            .post("https://slack.com/api/files.getUploadURLExternal")
+            .form(&params)
+            .bearer_auth(conf.slack_token.get())
+            .send()?;
+        check_slack_response(res)?
+    };
+    let upload_url = json["upload_url"].as_str().ok_or(BotError::SlackApiError("No upload_url in response".into()))?;
+    let file_id = json["file_id"].as_str().ok_or(BotError::SlackApiError("No file_id in response".into()))?;
+
+    // Transfer. Large files on slow links can take long, so only the
+    // (generous) upload_timeout applies. Timeouts are retried as transient errors.
+    info!("Transferring {:?} ({} bytes) to Slack", file, meta.len());
+    {
+        let _permit = conf.http_semaphore.acquire();
+        let body = reqwest::blocking::Body::sized(std::fs::File::open(file)?, meta.len());
+        slack_client(&conf.extra_headers)
+            .timeout(conf.upload_timeout)
+            .build()?
+            .post(upload_url)
+            .body(body)
+            .send()?
+            .error_for_status()?;
+    }
+    if let Err(e) = std::fs::create_dir_all(state_dir).and_then(|_| std::fs::write(&state_file, format!("{}\n{}\n", state_key, file_id))) {
+        warn!("Failed to save upload state to {:?}: {}", state_file, e);
+    }
+    Ok((file_id.to_string(), false))
+}
+
+/**
+ * Share transferred files to the channel with files.completeUploadExternal.
+ * It only takes conversation IDs, so names are resolved first (see
+ * `SlackLookup::conversation_id()` for the scopes that needs).
+ *
+ * @param files (Slack file ID, title) pairs
+ * @param target Channel, thread and comment
+ */
+fn complete(conf: &BotConfig, files: &[(String, Option<String>)], target: &Target) -> BotResult<serde_json::Value>
+{
+    let channel_id = conf.slack_lookup.conversation_id(target.channel)?
+        .ok_or_else(|| anyhow!("Channel not found: {:?}", target.channel))?;
+    let entries: Vec<serde_json::Value> = files.iter().map(|(id, title)| match title {
+        Some(title) => serde_json::json!({ "id": id, "title": title }),
+        None => serde_json::json!({ "id": id }),
+    }).collect();
+    let files_param = serde_json::Value::from(entries).to_string();
+    let mut params = vec![("files", files_param.as_str()), ("channel_id", channel_id.as_str())];
+    if let Some(text) = target.text {
+        params.push(("initial_comment", text));
+    }
+    if let Some(ts) = target.thread_ts {
+        params.push(("thread_ts", ts));
+    }
+
+    let _permit = conf.http_semaphore.acquire();
+    let res = slack_client(&conf.extra_headers).build()?
+        .post("https://slack.com/api/files.completeUploadExternal")
+        .form(&params)
+        .bearer_auth(conf.slack_token.get())
+        .send()?;
+    let mut json = check_slack_response(res)?;
+    json["file"] = json["files"][0].clone();
+    Ok(json)
+}
+
+fn state_path(state_dir: &Path, file: &Path) -> BotResult<PathBuf>
+{
+    let name = file.file_name().ok_or(anyhow!("Invalid file path"))?;
+    let mut state_name = name.to_os_string();
+    state_name.push(".upload");
+    Ok(state_dir.join(state_name))
+}
+
+fn clear_state(files: &[(PathBuf, Option<String>)], state_dir: &Path)
+{
+    for (path, _) in files {
+        if let Ok(state_file) = state_path(state_dir, path) {
+            let _ = std::fs::remove_file(state_file);
+        }
+    }
+}
+
+/**
+ * Read saved Slack file ID, if the state is for the same version of the file.
+ */
+fn read_state(state_file: &Path, state_key: &str) -> Option<String>
+{
+    let content = std::fs::read_to_string(state_file).ok()?;
+    let mut lines = content.lines();
+    if lines.next()? != state_key {
+        return None;
+    }
+    lines.next().map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
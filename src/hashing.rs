@@ -0,0 +1,112 @@
+use std::path::Path;
+use sha2::{Digest, Sha256};
+use log::debug;
+
+use crate::BotResult;
+
+/// Content hash for `{hash}` (`hash_algorithm`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Blake3,
+    Sha256,     // For compliance requirements, and `.sha256` companions
+}
+
+impl std::str::FromStr for HashAlgorithm {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            _ => Err(()),
+        }
+    }
+}
+
+impl HashAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Sha256 => "sha256",
+        }
+    }
+}
+
+/**
+ * Hex-encoded hash of a file's contents. With `cache`, the result is kept
+ * in an extended attribute (`user.slack-app-folder-echo.<algorithm>`, on
+ * Unix filesystems that support them) along with the file's size and
+ * mtime, and reused while those stay the same, so rescans of large files
+ * don't hash them again.
+ *
+ * @param path File to hash
+ * @param algorithm Hash algorithm
+ * @param cache Read and write the xattr cache (writing changes the file's ctime)
+ */
+pub fn hash_file(path: &Path, algorithm: HashAlgorithm, cache: bool) -> BotResult<String>
+{
+    let key = if cache { Some(cache_key(path)?) } else { None };
+    if let Some(hex) = key.as_deref().and_then(|k| cached(path, algorithm, k)) {
+        debug!("Using cached {} of {:?}", algorithm.name(), path);
+        return Ok(hex);
+    }
+    let mut file = std::fs::File::open(path)?;
+    let hex = match algorithm {
+        HashAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            std::io::copy(&mut file, &mut hasher)?;
+            hasher.finalize().to_hex().to_string()
+        },
+        HashAlgorithm::Sha256 => {
+            let mut hasher = Sha256::new();
+            std::io::copy(&mut file, &mut hasher)?;
+            format!("{:x}", hasher.finalize())
+        },
+    };
+    if let Some(key) = key {
+        store(path, algorithm, &key, &hex);
+    }
+    Ok(hex)
+}
+
+/// Size and mtime of a file, to tell if a cached hash is still valid
+fn cache_key(path: &Path) -> BotResult<String>
+{
+    let meta = std::fs::metadata(path)?;
+    let mtime = meta.modified()?.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    Ok(format!("{}:{}.{:09}", meta.len(), mtime.as_secs(), mtime.subsec_nanos()))
+}
+
+#[cfg(unix)]
+fn attr_name(algorithm: HashAlgorithm) -> String
+{
+    format!("user.{}.{}", crate::NAME, algorithm.name())
+}
+
+/// Cached hash from the xattr ("<key> <hex>"), if the key matches
+#[cfg(unix)]
+fn cached(path: &Path, algorithm: HashAlgorithm, key: &str) -> Option<String>
+{
+    let value = String::from_utf8(xattr::get(path, attr_name(algorithm)).ok()??).ok()?;
+    let (k, hex) = value.split_once(' ')?;
+    (k == key).then(|| hex.to_string())
+}
+
+#[cfg(unix)]
+fn store(path: &Path, algorithm: HashAlgorithm, key: &str, hex: &str)
+{
+    // Not all filesystems (or mount options) support user xattrs, it's only a cache
+    if let Err(e) = xattr::set(path, attr_name(algorithm), format!("{} {}", key, hex).as_bytes()) {
+        debug!("Couldn't cache hash in xattr of {:?}: {}", path, e);
+    }
+}
+
+#[cfg(not(unix))]
+fn cached(_path: &Path, _algorithm: HashAlgorithm, _key: &str) -> Option<String>
+{
+    None
+}
+
+#[cfg(not(unix))]
+fn store(_path: &Path, _algorithm: HashAlgorithm, _key: &str, _hex: &str)
+{
+}
@@ -0,0 +1,93 @@
+use std::{sync::atomic::Ordering, time::Duration};
+use log::{info, debug};
+use anyhow::anyhow;
+use tiny_http::{Header, Response, Server};
+
+use crate::{BotResult, control::{self, SectionToggles}, metrics};
+
+/// A running section whose bot loop hasn't checked in for this long is considered stuck
+/// (a single upload of a large file can legitimately take a while)
+pub const HEARTBEAT_MAX_AGE: Duration = Duration::from_secs(30 * 60);
+
+/**
+ * Serve health checks and metrics over HTTP (`--health-listen`), for
+ * Kubernetes probes and monitoring. Blocks forever.
+ *
+ *   GET /healthz - 200 unless a running section's bot loop is stuck
+ *                  (no pass in `HEARTBEAT_MAX_AGE`), 503 otherwise
+ *   GET /readyz  - 200 once every section is watching for files, 503 while
+ *                  starting, after a section has failed, or when shutting down
+ *   GET /metrics - Prometheus metrics of each section (see `metrics::render()`)
+ *
+ * Health checks reply with JSON detailing each section: liveness, readiness, queue
+ * depth and the time of the last successful post.
+ *
+ * @param addr Address to listen on, e.g. "0.0.0.0:9090"
+ * @param sections Toggles by section name
+ */
+pub fn serve(addr: &str, sections: SectionToggles) -> BotResult<()>
+{
+    let server = Server::http(addr).map_err(|e| anyhow!("Failed to listen for health checks on {}: {}", addr, e))?;
+    info!("Serving health checks on http://{}/healthz and /readyz, metrics on /metrics", addr);
+    for req in server.incoming_requests() {
+        let path = req.url().split('?').next().unwrap_or_default().to_string();
+        let (status, body) = match (req.method().clone(), path.as_str()) {
+            (tiny_http::Method::Get, "/healthz") => {
+                let (_, live, body) = report(&sections);
+                (if live { 200 } else { 503 }, body)
+            },
+            (tiny_http::Method::Get, "/readyz") => {
+                let (ready, _, body) = report(&sections);
+                (if ready { 200 } else { 503 }, body)
+            },
+            (tiny_http::Method::Get, "/metrics") => {
+                let res = Response::from_string(metrics::render(&sections.lock().unwrap()))
+                    .with_header(Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap());
+                if let Err(e) = req.respond(res) {
+                    debug!("Failed to send metrics: {}", e);
+                }
+                continue;
+            },
+            (tiny_http::Method::Get, _) => (404, serde_json::json!({ "ok": false, "error": "Not found" })),
+            _ => (405, serde_json::json!({ "ok": false, "error": "Only GET is supported" })),
+        };
+        let res = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+        if let Err(e) = req.respond(res) {
+            debug!("Failed to send health check response: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/**
+ * Check all sections.
+ *
+ * @return Whether all are ready, whether none are stuck, and the details as JSON
+ */
+fn report(sections: &SectionToggles) -> (bool, bool, serde_json::Value)
+{
+    let now = control::unix_now();
+    let time = |t: u64| if t == 0 { serde_json::Value::Null } else { serde_json::json!(t) };
+    let (mut all_ready, mut all_live) = (true, true);
+    let mut details = serde_json::Map::new();
+    for (name, t) in sections.lock().unwrap().iter() {
+        let stopped = t.stop.load(Ordering::Relaxed);
+        let ready = t.ready.load(Ordering::Relaxed) && !stopped;
+        let beat = t.heartbeat.load(Ordering::Relaxed);
+        // Not through its startup yet (beat 0) isn't stuck; a stopped section isn't expected to check in
+        let live = stopped || beat == 0 || now.saturating_sub(beat) <= HEARTBEAT_MAX_AGE.as_secs();
+        all_ready &= ready;
+        all_live &= live;
+        details.insert(name.clone(), serde_json::json!({
+            "live": live,
+            "ready": ready,
+            "stopped": stopped,
+            "heartbeat": time(beat),
+            "queue_depth": t.queue_depth.load(Ordering::Relaxed),
+            "last_posted": time(t.last_posted.load(Ordering::Relaxed)),
+        }));
+    }
+    (all_ready, all_live, serde_json::json!({ "ok": all_ready && all_live, "live": all_live, "ready": all_ready, "sections": details }))
+}
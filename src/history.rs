@@ -0,0 +1,92 @@
+use std::{collections::HashSet, io::Write, path::{Path, PathBuf}};
+use log::info;
+use anyhow::anyhow;
+
+use crate::{BotConfig, BotResult, check_slack_response, slack_client, with_retries};
+
+/**
+ * Names and sizes of files known to be in the channel already
+ * ("<size> <name>" per line), so they aren't posted again.
+ */
+#[derive(Debug, Clone)]
+pub struct PostedHistory {
+    path: PathBuf,
+}
+
+impl PostedHistory {
+    pub fn new(path: &Path) -> Self {
+        PostedHistory { path: path.to_path_buf() }
+    }
+
+    /**
+     * Load all known (size, name) pairs. A missing history is empty.
+     */
+    pub fn load(&self) -> BotResult<HashSet<(u64, String)>> {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashSet::new()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(content.lines()
+            .filter_map(|l| l.split_once(' '))
+            .filter_map(|(size, name)| Some((size.parse().ok()?, name.to_string())))
+            .collect())
+    }
+
+    pub fn record(&self, size: u64, name: &str) -> BotResult<()> {
+        let mut f = std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(f, "{} {}", size, name)?;
+        Ok(())
+    }
+
+    /**
+     * Check if a local file matches a known posted file.
+     */
+    pub fn contains(&self, file: &Path) -> BotResult<bool> {
+        let name = file.file_name().ok_or(anyhow!("Invalid file path"))?.to_string_lossy().to_string();
+        let size = std::fs::metadata(file)?.len();
+        Ok(self.load()?.contains(&(size, name)))
+    }
+
+    /**
+     * Add all files currently in the section's channel (files.list) to the history.
+     * Needs the `files:read` scope.
+     *
+     * @param conf Bot configuration
+     * @return Number of files added
+     */
+    pub fn import_from_slack(&self, conf: &BotConfig) -> BotResult<usize> {
+        let channel_id = conf.slack_lookup.channel_id(&conf.slack_channel)?
+            .ok_or_else(|| anyhow!("Channel not found: {:?}", conf.slack_channel))?;
+        let mut known = self.load()?;
+        let mut added = 0;
+        let mut page = 1;
+        loop {
+            let page_str = page.to_string();
+            let json = with_retries(conf, || {
+                let _permit = conf.http_semaphore.acquire();
+                let res = slack_client(&conf.extra_headers).build()?
+                    .get("https://slack.com/api/files.list")
+                    .query(&[("channel", channel_id.as_str()), ("count", "200"), ("page", page_str.as_str())])
+                    .bearer_auth(conf.slack_token.get())
+                    .send()?;
+                check_slack_response(res)
+            })?;
+            for f in json["files"].as_array().into_iter().flatten() {
+                if let (Some(name), Some(size)) = (f["name"].as_str(), f["size"].as_u64()) {
+                    if known.insert((size, name.to_string())) {
+                        self.record(size, name)?;
+                        added += 1;
+                    }
+                }
+            }
+            let pages = json["paging"]["pages"].as_u64().unwrap_or(1);
+            info!("Imported page {}/{} of files in {:?}", page, pages, conf.slack_channel);
+            if page >= pages {
+                break;
+            }
+            page += 1;
+        }
+        Ok(added)
+    }
+}
@@ -0,0 +1,187 @@
+use std::{collections::HashMap, net::TcpStream, path::PathBuf, sync::{Arc, Mutex, mpsc::Sender}, time::Duration};
+use log::{info, debug, warn};
+use anyhow::anyhow;
+use mailparse::{DispositionType, ParsedMail};
+
+use crate::{BotConfig, BotError, BotResult, control, parse_duration, secrets, source::{self, Source}};
+
+/// Default `imap_poll_interval`
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default `imap_search`
+const DEFAULT_SEARCH: &str = "UNSEEN";
+
+/**
+ * IMAP mailbox (`imap_url`) polled for messages matching `imap_search`.
+ * Attachments of matching messages are saved into the section's folder and
+ * posted like local files; the message is then marked read, or moved to
+ * `imap_move_to`, so "email this address and it shows up in the channel"
+ * works without anything else in between.
+ */
+pub struct ImapSource {
+    host: String,
+    port: u16,
+    user: String,
+    password: String,
+    mailbox: String,
+    search: String,
+    move_to: Option<String>,
+    poll_interval: Duration,
+    saved: Mutex<HashMap<u32, Vec<(usize, PathBuf)>>>,     // Attachments (by part index) saved from messages not marked yet, by UID
+}
+
+impl std::fmt::Debug for ImapSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ImapSource(imaps://{}@{}:{}/{}, {:?}, <redacted>)", self.user, self.host, self.port, self.mailbox, self.search)
+    }
+}
+
+impl ImapSource {
+    /**
+     * IMAP source from a config section.
+     *
+     * @return None if the section has no imap_url
+     */
+    pub fn from_config(section: &ini::Properties) -> BotResult<Option<Self>> {
+        let get = |key: &str| section.get(key).map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let url = match get("imap_url") {
+            Some(url) => reqwest::Url::parse(&url).map_err(|e| BotError::InvalidConfig(format!("Invalid imap_url: {}", e)))?,
+            None => return Ok(None),
+        };
+        if url.scheme() != "imaps" || url.password().is_some() || url.username().is_empty() {
+            return Err(BotError::InvalidConfig("imap_url must be like imaps://user@host/INBOX (password in imap_password)".to_string()));
+        }
+        let host = url.host_str().ok_or(BotError::InvalidConfig("No host in imap_url".to_string()))?.to_string();
+        let password = secrets::interpolate(&get("imap_password").ok_or(BotError::InvalidConfig("imap_url needs imap_password".to_string()))?)?.0;
+        let mailbox = match url.path().trim_matches('/') {
+            "" => "INBOX".to_string(),
+            m => m.to_string(),
+        };
+        let poll_interval = match get("imap_poll_interval") {
+            Some(s) => parse_duration(&s).ok_or(BotError::InvalidConfig(format!("Invalid imap_poll_interval: {:?}", s)))?,
+            None => DEFAULT_POLL_INTERVAL,
+        };
+        Ok(Some(ImapSource {
+            host,
+            port: url.port().unwrap_or(993),
+            user: url.username().to_string(),
+            password,
+            mailbox,
+            search: get("imap_search").unwrap_or_else(|| DEFAULT_SEARCH.to_string()),
+            move_to: get("imap_move_to"),
+            poll_interval,
+            saved: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /**
+     * Save the attachments of matching messages into the folder, and mark
+     * the messages read (or move them). If saving or marking a message fails,
+     * it's tried again on the next round, skipping the attachments already saved.
+     *
+     * @return Saved files
+     */
+    fn fetch_new(&self, conf: &BotConfig) -> BotResult<Vec<PathBuf>> {
+        let tls = native_tls::TlsConnector::new().map_err(|e| anyhow!("TLS: {}", e))?;
+        let client = ::imap::connect((self.host.as_str(), self.port), &self.host, &tls).map_err(imap_err)?;
+        let mut session = client.login(&self.user, &self.password)
+            .map_err(|(e, _)| anyhow!("IMAP login as {} failed: {}", self.user, e))?;
+        session.select(&self.mailbox).map_err(imap_err)?;
+        let mut uids: Vec<u32> = session.uid_search(&self.search).map_err(imap_err)?.into_iter().collect();
+        uids.sort();
+        debug!("{} message(s) in {} match {:?}", uids.len(), self.mailbox, self.search);
+
+        let mut paths = Vec::new();
+        for uid in uids {
+            let mut saved = self.saved.lock().unwrap().remove(&uid).unwrap_or_default();
+            let before = saved.len();
+            let res = self.fetch_message(&mut session, uid, conf, &mut saved);
+            paths.extend(saved[before..].iter().map(|(_, p)| p.clone()));
+            if let Err(e) = res {
+                warn!("Failed to process message {} in {}, trying again later: {}", uid, self.mailbox, e);
+                self.saved.lock().unwrap().insert(uid, saved);
+            }
+        }
+        let _ = session.logout();
+        Ok(paths)
+    }
+
+    /**
+     * Save one message's attachments and mark it read (or move it).
+     *
+     * @param saved Attachments already saved from the message, by part index; new ones are added
+     */
+    fn fetch_message(&self, session: &mut ::imap::Session<native_tls::TlsStream<TcpStream>>, uid: u32, conf: &BotConfig,
+        saved: &mut Vec<(usize, PathBuf)>) -> BotResult<()>
+    {
+        // PEEK, so a message that fails half way stays unread and is tried again
+        let fetches = session.uid_fetch(uid.to_string(), "BODY.PEEK[]").map_err(imap_err)?;
+        let body = match fetches.iter().next().and_then(|f| f.body()) {
+            Some(body) => body,
+            None => return Ok(()),
+        };
+        let mail = mailparse::parse_mail(body).map_err(|e| anyhow!("Failed to parse message {}: {}", uid, e))?;
+        save_attachments(&mail, conf, &mut 0, saved)?;
+        if saved.is_empty() {
+            info!("Message {} in {} has no attachments to post", uid, self.mailbox);
+        }
+        match &self.move_to {
+            Some(to) => session.uid_mv(uid.to_string(), to).map_err(imap_err)?,
+            None => { session.uid_store(uid.to_string(), "+FLAGS (\\Seen)").map_err(imap_err)?; },
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Save attachments (at any depth of a multipart message) that pass the section's filters.
+ *
+ * @param index Running index of attachment parts in the message
+ * @param out Saved attachments by part index; those already in it aren't saved again
+ */
+fn save_attachments(part: &ParsedMail, conf: &BotConfig, index: &mut usize, out: &mut Vec<(usize, PathBuf)>) -> BotResult<()>
+{
+    let disposition = part.get_content_disposition();
+    let name = disposition.params.get("filename").or_else(|| part.ctype.params.get("name"));
+    if let (DispositionType::Attachment | DispositionType::Inline, Some(name)) = (&disposition.disposition, name) {
+        // Only the last path component, never outside the folder
+        let name = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+        *index += 1;
+        if out.iter().any(|(i, _)| *i == *index) {
+            debug!("Attachment {:?} already saved", name);
+        } else if name.is_empty() || name.starts_with('.') {
+            warn!("Skipping attachment with an unusable name: {:?}", name);
+        } else if conf.file_filter.accepts(&conf.folder.join(name)) {
+            let mut path = conf.folder.join(name);
+            let mut n = 1;
+            while path.exists() {
+                path = conf.folder.join(format!("{}.{}", name, n));
+                n += 1;
+            }
+            let part_path = conf.folder.join(format!(".{}.part", name));
+            std::fs::write(&part_path, part.get_body_raw().map_err(|e| anyhow!("Failed to decode attachment {:?}: {}", name, e))?)?;
+            std::fs::rename(&part_path, &path)?;
+            info!("Saved email attachment: {:?}", path);
+            out.push((*index, path));
+        }
+    }
+    for sub in &part.subparts {
+        save_attachments(sub, conf, index, out)?;
+    }
+    Ok(())
+}
+
+impl Source for ImapSource {
+    fn scan(&self, conf: &BotConfig) -> BotResult<Vec<PathBuf>> {
+        self.fetch_new(conf)
+    }
+
+    fn watch(&self, conf: &BotConfig, paths_tx: Sender<PathBuf>, toggles: Arc<control::Toggles>) -> BotResult<()> {
+        info!("Polling imaps://{}@{}/{} every {:?}", self.user, self.host, self.mailbox, self.poll_interval);
+        source::poll(self.poll_interval, paths_tx, toggles, || self.fetch_new(conf))
+    }
+}
+
+fn imap_err(e: ::imap::Error) -> BotError {
+    anyhow!("IMAP: {}", e).into()
+}
@@ -0,0 +1,117 @@
+use std::{io::Write, path::Path};
+use anyhow::anyhow;
+
+use crate::{BotResult, BotSlackMessage, parse_config, post_message_with_retries, validate_token};
+
+/**
+ * Ask a question on the terminal.
+ *
+ * @param question Prompt text
+ * @param default Answer to use for an empty line, if any
+ */
+fn prompt(question: &str, default: Option<&str>) -> BotResult<String>
+{
+    loop {
+        match default {
+            Some(d) => print!("{} [{}]: ", question, d),
+            None => print!("{}: ", question),
+        }
+        std::io::stdout().flush()?;
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line)? == 0 {
+            return Err(anyhow!("Aborted (end of input)").into());
+        }
+        match (line.trim(), default) {
+            ("", Some(d)) => return Ok(d.to_string()),
+            ("", None) => println!("  (required)"),
+            (answer, _) => return Ok(answer.to_string()),
+        }
+    }
+}
+
+fn confirm(question: &str, default: bool) -> BotResult<bool>
+{
+    let answer = prompt(question, Some(if default { "Y/n" } else { "y/N" }))?;
+    Ok(match answer.to_lowercase().as_str() {
+        "y" | "yes" => true,
+        "n" | "no" => false,
+        _ => default,
+    })
+}
+
+/**
+ * Interactively create a config section and append it to an INI config
+ * file (created if missing). Optionally checks the token and posts
+ * a test message to the channel first.
+ *
+ * @param config_file INI file to write
+ */
+pub fn run(config_file: &Path) -> BotResult<()>
+{
+    let existing = if config_file.exists() {
+        let ext = config_file.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        if ext != "ini" && ext != "conf" {
+            return Err(anyhow!("init only writes INI files, not {:?}", config_file).into());
+        }
+        Some(ini::Ini::load_from_file(config_file)?)
+    } else {
+        None
+    };
+
+    println!("Adding a folder to {:?}. Press enter to accept [defaults].", config_file);
+    let section_name = prompt("Section name (e.g. team or purpose)", None)?;
+    if existing.as_ref().map(|c| c.section(Some(section_name.as_str())).is_some()).unwrap_or(false) {
+        return Err(anyhow!("Section {:?} already exists in {:?}", section_name, config_file).into());
+    }
+    let folder = prompt("Folder to watch", None)?;
+    if !Path::new(&folder).is_dir() {
+        if !confirm(&format!("{:?} doesn't exist. Create it?", folder), true)? {
+            return Err(anyhow!("Folder doesn't exist: {:?}", folder).into());
+        }
+        std::fs::create_dir_all(&folder)?;
+    }
+    let channel = prompt("Slack channel (#channel or @user)", None)?;
+    let bot_name = prompt("Bot name shown in Slack", Some(&section_name))?;
+    let limit = prompt("Max uploads per minute", Some("10"))?;
+    let token = prompt("Slack bot token (xoxb-...)", None)?;
+
+    let mut new_section = ini::Ini::new();
+    new_section.with_section(Some(section_name.as_str()))
+        .set("folder", folder.as_str())
+        .set("slack_channel", channel.as_str())
+        .set("bot_name", bot_name.as_str())
+        .set("limit_uploads_per_minute", limit.as_str())
+        .set("slack_token", token.as_str());
+    let conf = parse_config(&new_section)?.into_iter().next().ok_or(anyhow!("No section parsed"))?;
+
+    if confirm("Check the token and post a test message to the channel?", true)? {
+        let res = validate_token(&conf).and_then(|_| post_message_with_retries(&conf, &BotSlackMessage {
+            title: Some("Hello!".to_string()),
+            text: Some(format!("Files dropped in {} will be posted here.", folder)),
+            ..Default::default()
+        }));
+        match res {
+            Ok(_) => println!("Test message posted to {}.", channel),
+            Err(e) => {
+                println!("Test failed: {}", e);
+                if !confirm("Save the section anyway?", false)? {
+                    return Err(anyhow!("Not saved").into());
+                }
+            },
+        }
+    }
+
+    let mut text = Vec::new();
+    new_section.write_to(&mut text)?;
+    let mut options = std::fs::OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);   // Contains the token
+    let mut file = options.open(config_file)?;
+    if existing.is_some() {
+        file.write_all(b"\n")?;
+    }
+    file.write_all(&text)?;
+    println!("Saved section [{}] to {:?}.", section_name, config_file);
+    Ok(())
+}
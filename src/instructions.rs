@@ -0,0 +1,103 @@
+use std::path::Path;
+use log::{info, warn};
+use anyhow::anyhow;
+
+use crate::{BotConfig, BotError, BotResult, check_slack_response, icon_param, slack_client, with_retries};
+
+/**
+ * Render the `instructions_template` for a section. Literal "\n" in the
+ * (single-line INI) value becomes a line break.
+ *
+ * Placeholders: {folder}, {section}, {channel}, {bot_name},
+ * {limit_uploads_per_minute}, {unfriendly_extensions}, {max_file_size}
+ */
+pub fn render(conf: &BotConfig, template: &str) -> BotResult<String>
+{
+    let text = crate::template::render(template, |name| Ok::<_, BotError>(Some(match name {
+        "folder" => conf.folder.to_string_lossy().to_string(),
+        "section" => conf.section.clone(),
+        "channel" => conf.slack_channel.clone(),
+        "bot_name" => conf.bot_name.clone(),
+        "limit_uploads_per_minute" => conf.limit_uploads_per_minute.to_string(),
+        "unfriendly_extensions" => conf.unfriendly_extensions.iter()
+            .map(|e| format!(".{}", e)).collect::<Vec<_>>().join(", "),
+        "max_file_size" => conf.max_file_size.map(crate::template::human_size).unwrap_or_else(|| "unlimited".to_string()),
+        _ => return Ok(None),
+    })))?;
+    Ok(text.replace("\\n", "\n"))
+}
+
+/**
+ * Make sure the channel has an up-to-date pinned instructions message.
+ * The first run posts and pins it; later runs edit it with chat.update
+ * if the rendered text has changed (e.g. after a config change).
+ *
+ * State file contents: "<channel id> <message ts>\n<text>"
+ *
+ * @param conf Bot configuration (with `instructions_template` set)
+ * @param state_file Where to remember the posted message
+ */
+pub fn ensure_pinned(conf: &BotConfig, state_file: &Path) -> BotResult<()>
+{
+    let template = conf.instructions_template.as_ref().ok_or(anyhow!("No instructions_template"))?;
+    let text = render(conf, template)?;
+
+    if let Some((channel_id, ts, old_text)) = read_state(state_file) {
+        if old_text == text {
+            info!("Pinned instructions for {:?} are up to date", conf.section);
+            return Ok(());
+        }
+        match with_retries(conf, || call(conf, "chat.update", &[("channel", channel_id.as_str()), ("ts", ts.as_str()), ("text", text.as_str())])) {
+            Ok(_) => {
+                info!("Updated pinned instructions for {:?}", conf.section);
+                return write_state(state_file, &channel_id, &ts, &text);
+            },
+            Err(BotError::SlackApiError(e)) if e == "message_not_found" || e == "channel_not_found" => {
+                warn!("Pinned instructions message is gone ({}), posting a new one", e);
+            },
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut params = vec![
+        ("channel", conf.slack_channel.clone()),
+        ("username", conf.bot_name.clone()),
+        ("text", text.clone()),
+    ];
+    if let Some(icon) = &conf.bot_icon {
+        let (key, val) = icon_param(icon);
+        params.push((key, val));
+    }
+    let params: Vec<(&str, &str)> = params.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    let json = with_retries(conf, || call(conf, "chat.postMessage", &params))?;
+    let channel_id = json["channel"].as_str().ok_or(BotError::SlackApiError("No channel in response".into()))?;
+    let ts = json["ts"].as_str().ok_or(BotError::SlackApiError("No ts in response".into()))?;
+    with_retries(conf, || call(conf, "pins.add", &[("channel", channel_id), ("timestamp", ts)]))?;
+    info!("Posted and pinned instructions for {:?}", conf.section);
+    write_state(state_file, channel_id, ts, &text)
+}
+
+fn call(conf: &BotConfig, method: &str, params: &[(&str, &str)]) -> BotResult<serde_json::Value>
+{
+    let _permit = conf.http_semaphore.acquire();
+    let res = slack_client(&conf.extra_headers).build()?
+        .post(format!("https://slack.com/api/{}", method))
+        .form(params)
+        .bearer_auth(conf.slack_token.get())
+        .send()?;
+    check_slack_response(res)
+}
+
+fn read_state(state_file: &Path) -> Option<(String, String, String)>
+{
+    let content = std::fs::read_to_string(state_file).ok()?;
+    let (first, text) = content.split_once('\n')?;
+    let (channel_id, ts) = first.split_once(' ')?;
+    Some((channel_id.to_string(), ts.to_string(), text.to_string()))
+}
+
+fn write_state(state_file: &Path, channel_id: &str, ts: &str, text: &str) -> BotResult<()>
+{
+    std::fs::write(state_file, format!("{} {}\n{}", channel_id, ts, text))?;
+    Ok(())
+}
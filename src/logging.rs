@@ -0,0 +1,303 @@
+#[cfg(unix)]
+use std::str::FromStr;
+use std::{cell::RefCell, fs::File, io::Write, path::{Path, PathBuf}, sync::{Mutex, atomic::{AtomicUsize, Ordering}}};
+use anyhow::anyhow;
+use log::LevelFilter;
+
+use crate::BotResult;
+
+/// Levels by their `LevelFilter as usize`
+const LEVELS: [LevelFilter; 6] = [LevelFilter::Off, LevelFilter::Error, LevelFilter::Warn, LevelFilter::Info, LevelFilter::Debug, LevelFilter::Trace];
+
+/// Level for everything without a section level of its own (`--debug`, control socket)
+static LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Info as usize);
+/// Sections with their own level (`log_level`)
+static SECTION_LEVELS: Mutex<Vec<(String, LevelFilter)>> = Mutex::new(Vec::new());
+
+thread_local! {
+    /// Section whose worker (or file watcher) runs in this thread
+    static SECTION: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/**
+ * Logs the messages of section threads with the section's name as the
+ * target (shown in place of the module name), filtered by the section's own
+ * `log_level` if it has one. Everything else goes by the global level.
+ */
+struct SectionLogger {
+    inner: Box<dyn log::Log>,
+}
+
+fn section_level(section: &str) -> Option<LevelFilter>
+{
+    SECTION_LEVELS.lock().unwrap().iter().find(|(s, _)| s == section).map(|(_, l)| *l)
+}
+
+impl log::Log for SectionLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        let level = SECTION.with(|s| s.borrow().as_deref().and_then(section_level)).unwrap_or_else(level);
+        metadata.level() <= level && self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        SECTION.with(|s| match s.borrow().as_deref() {
+            Some(section) => self.inner.log(&log::Record::builder()
+                .args(*record.args())
+                .level(record.level())
+                .target(section)
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line())
+                .build()),
+            None => self.inner.log(record),
+        });
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/**
+ * Install the logger (stderr, file, syslog or event log) behind section
+ * targets and levels. It should pass everything; what's logged is set with
+ * `set_level()` and `set_section_level()`.
+ */
+pub fn install(inner: Box<dyn log::Log>) -> BotResult<()>
+{
+    log::set_boxed_logger(Box::new(SectionLogger { inner }))
+        .map_err(|e| anyhow!("Failed to set up logging: {}", e))?;
+    update_max_level();
+    Ok(())
+}
+
+/// Let log macros through up to the most verbose level in use
+fn update_max_level()
+{
+    let most = SECTION_LEVELS.lock().unwrap().iter().map(|(_, l)| *l).chain(std::iter::once(level())).max();
+    log::set_max_level(most.unwrap_or(LevelFilter::Info));
+}
+
+/// Global log level
+pub fn level() -> LevelFilter
+{
+    LEVELS[LEVEL.load(Ordering::Relaxed).min(LEVELS.len() - 1)]
+}
+
+/// Set the global log level (sections with their own `log_level` keep it)
+pub fn set_level(level: LevelFilter)
+{
+    LEVEL.store(level as usize, Ordering::Relaxed);
+    update_max_level();
+}
+
+/**
+ * Set or clear (None) a section's own log level.
+ */
+pub fn set_section_level(section: &str, level: Option<LevelFilter>)
+{
+    {
+        let mut levels = SECTION_LEVELS.lock().unwrap();
+        levels.retain(|(s, _)| s != section);
+        if let Some(level) = level {
+            levels.push((section.to_string(), level));
+        }
+    }
+    update_max_level();
+}
+
+/**
+ * Mark the current thread as working for a section, so its messages are
+ * logged with the section as the target, at the section's level.
+ */
+pub fn set_section(section: &str)
+{
+    SECTION.with(|s| *s.borrow_mut() = Some(section.to_string()));
+}
+
+/// When to start a new log file (`--log-rotate`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rotation {
+    Never,
+    Size(u64),      // When the file would grow over this many bytes
+    Daily,          // At the first message after midnight (local time)
+}
+
+impl std::str::FromStr for Rotation {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "never" => Ok(Rotation::Never),
+            "daily" => Ok(Rotation::Daily),
+            s => crate::parse_size(s).filter(|n| *n > 0).map(Rotation::Size).ok_or(()),
+        }
+    }
+}
+
+/**
+ * Log file that rotates itself: "app.log" is renamed to "app.log.1", the
+ * earlier "app.log.1" to "app.log.2" and so on, keeping `keep` old files.
+ */
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    keep: usize,
+    file: Option<File>,
+    size: u64,
+    day: chrono::NaiveDate,
+}
+
+impl RotatingFile {
+    /// Open (append to) a log file
+    pub fn open(path: &Path, rotation: Rotation, keep: usize) -> BotResult<Self> {
+        let file = Self::open_file(path)?;
+        let size = file.metadata()?.len();
+        let day = file.metadata()?.modified().map(|t| chrono::DateTime::<chrono::Local>::from(t).date_naive())
+            .unwrap_or_else(|_| chrono::Local::now().date_naive());
+        Ok(RotatingFile { path: path.to_path_buf(), rotation, keep, file: Some(file), size, day })
+    }
+
+    fn open_file(path: &Path) -> BotResult<File> {
+        Ok(std::fs::OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|e| anyhow!("Failed to open --log-file {:?}: {}", path, e))?)
+    }
+
+    fn numbered(&self, n: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file = None;   // Closed first, Windows can't rename open files
+        let _ = std::fs::remove_file(self.numbered(self.keep));
+        for n in (1..self.keep).rev() {
+            let from = self.numbered(n);
+            if from.exists() {
+                std::fs::rename(&from, self.numbered(n + 1))?;
+            }
+        }
+        if self.keep > 0 {
+            std::fs::rename(&self.path, self.numbered(1))?;
+        } else {
+            std::fs::remove_file(&self.path)?;
+        }
+        self.file = Some(std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?);
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let today = chrono::Local::now().date_naive();
+        let due = match self.rotation {
+            Rotation::Never => false,
+            Rotation::Size(max) => self.size > 0 && self.size + buf.len() as u64 > max,
+            Rotation::Daily => today != self.day && self.size > 0,
+        };
+        self.day = today;
+        // If rotating fails (e.g. a full disk), keep logging to the current file and try again on the next message
+        if due {
+            if let Err(e) = self.rotate() {
+                eprintln!("Failed to rotate log file {:?}: {}", self.path, e);
+            }
+        }
+        if self.file.is_none() {
+            self.file = Some(std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?);
+        }
+        let n = self.file.as_mut().map(|f| f.write(buf)).unwrap_or(Ok(0))?;
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.as_mut().map(|f| f.flush()).unwrap_or(Ok(()))
+    }
+}
+
+/**
+ * Log to a file (`--log-file`) instead of stderr, rotating it by size or
+ * daily. Panics are logged too, as stderr may not be seen (cron, --daemon).
+ *
+ * @param path Log file, appended to
+ * @param rotation When to start a new file
+ * @param keep How many old files to keep
+ */
+pub fn init_file(path: &Path, rotation: Rotation, keep: usize) -> BotResult<()>
+{
+    let file = RotatingFile::open(path, rotation, keep)?;
+    install(Box::new(env_logger::builder()
+        .filter_level(LevelFilter::Trace)
+        .target(env_logger::Target::Pipe(Box::new(file)))
+        .write_style(env_logger::WriteStyle::Never)
+        .build()))?;
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        log::error!("{}", info);
+        default_hook(info);
+    }));
+    Ok(())
+}
+
+/**
+ * Send the log to the local syslog daemon (`--syslog`) instead of stderr,
+ * like other long-running services on traditional servers. Messages keep
+ * their levels (error, warning, info, debug).
+ *
+ * @param facility Syslog facility, e.g. "daemon" or "local3"
+ * @param tag Program name shown in the log (empty = our name)
+ */
+#[cfg(unix)]
+pub fn init_syslog(facility: &str, tag: &str) -> BotResult<()>
+{
+    let facility = syslog::Facility::from_str(&facility.to_lowercase())
+        .map_err(|_| anyhow!("Invalid --syslog-facility (expected e.g. daemon, user or local0..local7): {:?}", facility))?;
+    let formatter = syslog::Formatter3164 {
+        facility,
+        hostname: None,
+        process: if tag.is_empty() { crate::NAME.to_string() } else { tag.to_string() },
+        pid: std::process::id(),
+    };
+    let logger = syslog::unix(formatter).map_err(|e| anyhow!("Failed to connect to syslog: {}", e))?;
+    install(Box::new(SyslogLogger(Mutex::new(logger))))
+}
+
+/**
+ * Like `syslog::BasicLogger`, but with the PID of the current process in
+ * each message, as `--daemon` forks after logging has been set up.
+ */
+#[cfg(unix)]
+struct SyslogLogger(Mutex<syslog::Logger<syslog::LoggerBackend, syslog::Formatter3164>>);
+
+#[cfg(unix)]
+impl log::Log for SyslogLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        let mut logger = self.0.lock().unwrap();
+        logger.formatter.pid = std::process::id();
+        let message = record.args().to_string();
+        let _ = match record.level() {
+            log::Level::Error => logger.err(message),
+            log::Level::Warn => logger.warning(message),
+            log::Level::Info => logger.info(message),
+            log::Level::Debug | log::Level::Trace => logger.debug(message),
+        };
+    }
+
+    fn flush(&self) {
+        let _ = self.0.lock().unwrap().backend.flush();
+    }
+}
+
+#[cfg(not(unix))]
+pub fn init_syslog(_facility: &str, _tag: &str) -> BotResult<()>
+{
+    Err(anyhow!("--syslog is only supported on Unix-like systems").into())
+}
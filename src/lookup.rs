@@ -0,0 +1,304 @@
+use std::{collections::HashMap, hash::Hash, sync::{Arc, Mutex}, time::{Duration, Instant}};
+use log::debug;
+use anyhow::anyhow;
+
+use crate::{BotError, BotResult, secrets::Token, semaphore::Semaphore, slack_client};
+
+const CHANNEL_CACHE_TTL: Duration = Duration::from_secs(15 * 60);
+const USER_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+const USERGROUP_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+const EMOJI_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+type NameMap = Arc<HashMap<String, String>>;
+
+/**
+ * Check if a string looks like a Slack object ID ("C0123ABCD", "U0123ABCD", ...)
+ * with one of the given prefixes.
+ */
+pub fn looks_like_id(s: &str, prefixes: &[char]) -> bool {
+    s.len() >= 9 && s.starts_with(prefixes)
+        && s.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+/**
+ * Thread-safe map whose entries expire after a fixed time.
+ */
+#[derive(Debug)]
+pub struct TtlCache<K, V> {
+    ttl: Duration,
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    pub fn new(ttl: Duration) -> Self {
+        TtlCache { ttl, entries: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(key)
+            .filter(|(t, _)| t.elapsed() < self.ttl)
+            .map(|(_, v)| v.clone())
+    }
+
+    pub fn insert(&self, key: K, val: V) {
+        self.entries.lock().unwrap().insert(key, (Instant::now(), val));
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /**
+     * Get cached value or compute (and cache) it with `f`.
+     */
+    pub fn get_or_try_insert<F>(&self, key: K, f: F) -> BotResult<V>
+        where F: FnOnce() -> BotResult<V>
+    {
+        if let Some(v) = self.get(&key) {
+            return Ok(v);
+        }
+        let v = f()?;
+        self.insert(key, v.clone());
+        Ok(v)
+    }
+}
+
+
+/**
+ * Cached name -> ID lookups against Slack read APIs, shared by all
+ * sections that use the same token.
+ */
+#[derive(Debug)]
+pub struct SlackLookup {
+    token: Token,
+    http_semaphore: Arc<Semaphore>,
+    extra_headers: reqwest::header::HeaderMap,
+    channels: TtlCache<(), NameMap>,
+    users_by_name: TtlCache<(), NameMap>,
+    users_by_email: TtlCache<String, Option<String>>,
+    usergroups: TtlCache<(), NameMap>,
+    custom_emoji: TtlCache<(), NameMap>,
+    dm_channels: TtlCache<String, String>,      // User ID -> DM channel ID
+    team: Mutex<Option<String>>,      // Workspace ID, doesn't change for a token
+}
+
+impl SlackLookup {
+    pub fn new(token: Token, http_semaphore: Arc<Semaphore>, extra_headers: reqwest::header::HeaderMap) -> Self {
+        SlackLookup {
+            token,
+            http_semaphore,
+            extra_headers,
+            channels: TtlCache::new(CHANNEL_CACHE_TTL),
+            users_by_name: TtlCache::new(USER_CACHE_TTL),
+            users_by_email: TtlCache::new(USER_CACHE_TTL),
+            usergroups: TtlCache::new(USERGROUP_CACHE_TTL),
+            custom_emoji: TtlCache::new(EMOJI_CACHE_TTL),
+            dm_channels: TtlCache::new(USER_CACHE_TTL),
+            team: Mutex::new(None),
+        }
+    }
+
+    /**
+     * Call a Slack Web API read method.
+     */
+    fn get_json(&self, method: &str, params: &[(&str, &str)]) -> BotResult<serde_json::Value> {
+        debug!("Slack lookup: {} {:?}", method, params);
+        let _permit = self.http_semaphore.acquire();
+        let res = slack_client(&self.extra_headers).build()?
+            .get(format!("https://slack.com/api/{}", method))
+            .query(params)
+            .bearer_auth(self.token.get())
+            .send()?;
+        crate::check_slack_response(res)
+    }
+
+    /**
+     * Call a cursor-paginated list method and collect name -> ID pairs
+     * from each item with `extract`.
+     */
+    fn list_names<F>(&self, method: &str, params: &[(&str, &str)], list_key: &str, extract: F) -> BotResult<NameMap>
+        where F: Fn(&serde_json::Value) -> Vec<(String, String)>
+    {
+        let mut names = HashMap::new();
+        let mut cursor = String::new();
+        loop {
+            let mut p = params.to_vec();
+            if !cursor.is_empty() {
+                p.push(("cursor", cursor.as_str()));
+            }
+            let json = self.get_json(method, &p)?;
+            for item in json[list_key].as_array().into_iter().flatten() {
+                names.extend(extract(item));
+            }
+            cursor = json["response_metadata"]["next_cursor"].as_str().unwrap_or("").to_string();
+            if cursor.is_empty() {
+                break;
+            }
+        }
+        Ok(Arc::new(names))
+    }
+
+    /**
+     * Resolve a channel name (with or without '#') to its ID.
+     * Channel IDs (e.g. "C0123ABCD") are returned as-is.
+     */
+    pub fn channel_id(&self, name: &str) -> BotResult<Option<String>> {
+        if looks_like_id(name, &['C', 'G']) {
+            return Ok(Some(name.to_string()));
+        }
+        let channels = self.channels.get_or_try_insert((), || {
+            self.list_names("conversations.list",
+                &[("types", "public_channel,private_channel"), ("exclude_archived", "true"), ("limit", "1000")],
+                "channels",
+                |c| match (c["name"].as_str(), c["id"].as_str()) {
+                    (Some(name), Some(id)) => vec![(name.to_string(), id.to_string())],
+                    _ => vec![],
+                })
+        })?;
+        Ok(channels.get(name.trim_start_matches('#')).cloned())
+    }
+
+    /**
+     * ID of the workspace ("T0123ABCD") the token belongs to, from auth.test
+     * unless it's already known.
+     */
+    pub fn team_id(&self) -> BotResult<String> {
+        if let Some(team) = self.cached_team_id() {
+            return Ok(team);
+        }
+        let json = self.get_json("auth.test", &[])?;
+        let team = json["team_id"].as_str().ok_or(anyhow!("auth.test returned no team_id"))?;
+        self.set_team_id(team);
+        Ok(team.to_string())
+    }
+
+    /// Workspace ID, if already looked up (never calls Slack)
+    pub fn cached_team_id(&self) -> Option<String> {
+        self.team.lock().unwrap().clone()
+    }
+
+    /// Remember the workspace ID, e.g. from another auth.test call
+    pub fn set_team_id(&self, team: &str) {
+        *self.team.lock().unwrap() = Some(team.to_string());
+    }
+
+    /**
+     * Resolve a destination to a conversation ID for methods that only take
+     * IDs (e.g. files.completeUploadExternal). Channel and DM IDs ("C...",
+     * "G...", "D...") are returned as-is, users ("@name" or "U...") get their
+     * DM channel with conversations.open, and channel names are looked up.
+     * Needs `channels:read` / `groups:read` for names, `im:write` (and
+     * `users:read`) for users.
+     */
+    pub fn conversation_id(&self, dest: &str) -> BotResult<Option<String>> {
+        if looks_like_id(dest, &['C', 'G', 'D']) {
+            return Ok(Some(dest.to_string()));
+        }
+        let user = if looks_like_id(dest, &['U', 'W']) {
+            Some(dest.to_string())
+        } else if dest.starts_with('@') {
+            match self.user_id_by_name(dest)? {
+                Some(id) => Some(id),
+                None => return Ok(None),
+            }
+        } else {
+            None
+        };
+        match user {
+            Some(user) => self.dm_channels.get_or_try_insert(user.clone(), || {
+                let json = self.get_json("conversations.open", &[("users", user.as_str())])?;
+                json["channel"]["id"].as_str().map(|s| s.to_string())
+                    .ok_or_else(|| anyhow!("conversations.open returned no channel for {:?}", user).into())
+            }).map(Some),
+            None => self.channel_id(dest),
+        }
+    }
+
+    /**
+     * Resolve a user's handle or display name (with or without '@') to their ID.
+     */
+    pub fn user_id_by_name(&self, name: &str) -> BotResult<Option<String>> {
+        let users = self.users_by_name.get_or_try_insert((), || {
+            self.list_names("users.list", &[("limit", "1000")], "members", |u| {
+                let id = match u["id"].as_str() {
+                    Some(id) => id.to_string(),
+                    None => return vec![],
+                };
+                [&u["name"], &u["profile"]["display_name"]].iter()
+                    .filter_map(|n| n.as_str())
+                    .filter(|n| !n.is_empty())
+                    .map(|n| (n.to_string(), id.clone()))
+                    .collect()
+            })
+        })?;
+        Ok(users.get(name.trim_start_matches('@')).cloned())
+    }
+
+    /**
+     * Resolve a user's email address to their ID.
+     */
+    pub fn user_id_by_email(&self, email: &str) -> BotResult<Option<String>> {
+        self.users_by_email.get_or_try_insert(email.to_string(), || {
+            match self.get_json("users.lookupByEmail", &[("email", email)]) {
+                Ok(json) => Ok(json["user"]["id"].as_str().map(|s| s.to_string())),
+                Err(BotError::SlackApiError(e)) if e == "users_not_found" => Ok(None),
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    /**
+     * Resolve a user group handle (with or without '@') to its ID.
+     */
+    pub fn usergroup_id(&self, handle: &str) -> BotResult<Option<String>> {
+        let groups = self.usergroups.get_or_try_insert((), || {
+            self.list_names("usergroups.list", &[], "usergroups",
+                |g| match (g["handle"].as_str(), g["id"].as_str()) {
+                    (Some(handle), Some(id)) => vec![(handle.to_string(), id.to_string())],
+                    _ => vec![],
+                })
+        })?;
+        Ok(groups.get(handle.trim_start_matches('@')).cloned())
+    }
+
+    /**
+     * Check if the workspace has a custom emoji (or alias) with this name,
+     * with or without colons. Standard emoji aren't listed by emoji.list,
+     * so `false` doesn't mean the emoji can't be used.
+     */
+    pub fn has_custom_emoji(&self, name: &str) -> BotResult<bool> {
+        let emoji = self.custom_emoji.get_or_try_insert((), || {
+            let json = self.get_json("emoji.list", &[])?;
+            Ok(Arc::new(json["emoji"].as_object().into_iter().flatten()
+                .map(|(name, val)| (name.clone(), val.as_str().unwrap_or_default().to_string()))
+                .collect()))
+        })?;
+        Ok(emoji.contains_key(name.trim_matches(':')))
+    }
+
+    /**
+     * Drop cached lookups that a Slack error suggests are stale
+     * (e.g. a renamed or archived channel).
+     */
+    pub fn invalidate_on_error(&self, err: &BotError) {
+        if let BotError::SlackApiError(e) = err {
+            match e.as_str() {
+                "channel_not_found" | "not_in_channel" | "is_archived" => {
+                    debug!("Invalidating channel cache after error: {}", e);
+                    self.channels.clear();
+                },
+                "user_not_found" | "users_not_found" | "user_not_visible" => {
+                    debug!("Invalidating user caches after error: {}", e);
+                    self.users_by_name.clear();
+                    self.users_by_email.clear();
+                },
+                "subteam_not_found" | "no_such_subteam" => {
+                    debug!("Invalidating usergroup cache after error: {}", e);
+                    self.usergroups.clear();
+                },
+                _ => {},
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::{io::Write, path::{Path, PathBuf}};
+
+use crate::BotResult;
+
+/// Marker between the original file name and the addendum's own extension
+const ADDENDUM_MARKER: &str = ".addendum";
+
+/**
+ * Where each posted file ended up in Slack ("<name>\t<channel id>\t<ts>"
+ * per line), so follow-up files can be posted in the original's thread.
+ */
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    path: PathBuf,
+}
+
+impl Manifest {
+    pub fn new(path: &Path) -> Self {
+        Manifest { path: path.to_path_buf() }
+    }
+
+    pub fn record(&self, name: &str, channel: &str, ts: &str) -> BotResult<()> {
+        let mut f = std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(f, "{}\t{}\t{}", name, channel, ts)?;
+        Ok(())
+    }
+
+    /**
+     * Find the latest post of a file by name.
+     *
+     * @return (channel id, message ts)
+     */
+    pub fn lookup(&self, name: &str) -> Option<(String, String)> {
+        let content = std::fs::read_to_string(&self.path).ok()?;
+        content.lines().rev()
+            .filter_map(|l| {
+                let mut parts = l.splitn(3, '\t');
+                Some((parts.next()?, parts.next()?, parts.next()?))
+            })
+            .find(|(n, _, _)| *n == name)
+            .map(|(_, channel, ts)| (channel.to_string(), ts.to_string()))
+    }
+}
+
+/**
+ * Name of the original file an addendum refers to: "report.pdf.addendum.txt"
+ * and "report.pdf.addendum" both refer to "report.pdf".
+ */
+pub fn addendum_target(name: &str) -> Option<&str> {
+    let pos = name.rfind(ADDENDUM_MARKER)?;
+    let rest = &name[pos + ADDENDUM_MARKER.len()..];
+    if pos == 0 || !(rest.is_empty() || (rest.starts_with('.') && !rest[1..].contains('.'))) {
+        return None;
+    }
+    Some(&name[..pos])
+}
+
+/**
+ * Channel ID and message ts of a post, for replying in its thread. Works
+ * for chat.postMessage responses and shared files (files.upload,
+ * files.completeUploadExternal -- though shares of the latter may not be
+ * reported yet).
+ *
+ * @param response API response
+ * @param file The posted file's object in the response
+ */
+pub fn message_ref(response: &serde_json::Value, file: &serde_json::Value) -> Option<(String, String)> {
+    if let (Some(channel), Some(ts)) = (response["channel"].as_str(), response["ts"].as_str()) {
+        return Some((channel.to_string(), ts.to_string()));
+    }
+    ["public", "private"].iter()
+        .filter_map(|kind| file["shares"][*kind].as_object())
+        .flatten()
+        .find_map(|(channel, shares)| shares[0]["ts"].as_str().map(|ts| (channel.clone(), ts.to_string())))
+}
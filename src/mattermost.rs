@@ -0,0 +1,180 @@
+use std::sync::Mutex;
+use log::{info, error};
+use anyhow::anyhow;
+
+use crate::{BotConfig, BotError, BotResult, BotSlackMessage, destination::Destination, secrets};
+
+/// Mattermost's default limit for post messages
+const MESSAGE_MAX_CHARS: usize = 16383;
+
+/**
+ * Mattermost channel, posted to with a bot or personal access token
+ * (`mattermost_url`, `mattermost_token`, `mattermost_channel`).
+ */
+pub struct Mattermost {
+    url: String,                    // Server base URL, without trailing slash
+    token: String,
+    channel: String,                // Channel ID, or "<team>/<channel name>"
+    channel_id: Mutex<Option<String>>,      // Resolved from `channel`
+}
+
+impl std::fmt::Debug for Mattermost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Mattermost({}, channel {}, <redacted>)", self.url, self.channel)
+    }
+}
+
+impl Mattermost {
+    /**
+     * Mattermost destination from a config section.
+     *
+     * @return None if the section has no Mattermost keys
+     */
+    pub fn from_config(section: &ini::Properties) -> BotResult<Option<Self>> {
+        let get = |key: &str| section.get(key).map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        match (get("mattermost_url"), get("mattermost_token"), get("mattermost_channel")) {
+            (None, None, None) => Ok(None),
+            (Some(url), Some(token), Some(channel)) => {
+                if !url.starts_with("https://") && !url.starts_with("http://") {
+                    return Err(BotError::InvalidConfig(format!("mattermost_url {:?} isn't an http(s) URL", url)));
+                }
+                Ok(Some(Mattermost {
+                    url: url.trim_end_matches('/').to_string(),
+                    token: secrets::interpolate(&token)?.0,
+                    channel,
+                    channel_id: Mutex::new(None),
+                }))
+            },
+            _ => Err(BotError::InvalidConfig("Mattermost needs mattermost_url, mattermost_token and mattermost_channel".to_string())),
+        }
+    }
+
+    fn client(&self) -> BotResult<reqwest::blocking::Client> {
+        Ok(reqwest::blocking::Client::builder().build()?)
+    }
+
+    /**
+     * ID of the configured channel, looking up "<team>/<channel name>" on first use.
+     */
+    fn channel_id(&self, conf: &BotConfig) -> BotResult<String> {
+        let mut cached = self.channel_id.lock().unwrap();
+        if let Some(id) = cached.as_ref() {
+            return Ok(id.clone());
+        }
+        let id = match self.channel.split_once('/') {
+            None => self.channel.clone(),
+            Some((team, name)) => {
+                let _permit = conf.http_semaphore.acquire();
+                let res = self.client()?
+                    .get(format!("{}/api/v4/teams/name/{}/channels/name/{}", self.url, team, name.trim_start_matches('~')))
+                    .bearer_auth(&self.token)
+                    .send()?;
+                check_response(res)?["id"].as_str()
+                    .ok_or(anyhow!("No channel ID for {:?} in Mattermost response", self.channel))?
+                    .to_string()
+            },
+        };
+        *cached = Some(id.clone());
+        Ok(id)
+    }
+
+    /**
+     * Create a post, with already uploaded files.
+     */
+    fn create_post(&self, conf: &BotConfig, msg: &BotSlackMessage, channel_id: &str, file_ids: &[String]) -> BotResult<serde_json::Value> {
+        let mut post = serde_json::json!({
+            "channel_id": channel_id,
+            "message": message(msg),
+            "file_ids": file_ids,
+            "props": { "override_username": conf.bot_name },
+        });
+        if let Some(root_id) = &msg.thread_ts {
+            post["root_id"] = root_id.clone().into();
+        }
+        if let Some(icon) = msg.icon.as_ref().or(conf.bot_icon.as_ref()).filter(|i| i.starts_with("https://") || i.starts_with("http://")) {
+            post["props"]["override_icon_url"] = icon.clone().into();
+        }
+        let _permit = conf.http_semaphore.acquire();
+        let res = self.client()?
+            .post(format!("{}/api/v4/posts", self.url))
+            .bearer_auth(&self.token)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(post.to_string())
+            .send()?;
+        let post = check_response(res)?;
+        info!("Got Ok from Mattermost");
+        let post_id = post["id"].as_str().unwrap_or_default().to_string();
+        let mut res = serde_json::json!({ "ok": true, "channel": channel_id, "ts": post_id, "post": post });
+        if let Some(file_id) = file_ids.first() {
+            res["file"] = serde_json::json!({ "id": file_id, "permalink": format!("{}/_redirect/pl/{}", self.url, post_id) });
+        }
+        Ok(res)
+    }
+}
+
+impl Destination for Mattermost {
+    fn label(&self) -> String {
+        format!("mattermost:{}", self.channel)
+    }
+
+    fn post_text(&self, conf: &BotConfig, msg: &BotSlackMessage) -> BotResult<serde_json::Value> {
+        info!("Posting message to Mattermost: {:?}", &msg);
+        let channel_id = msg.channel.clone().map(Ok).unwrap_or_else(|| self.channel_id(conf))?;
+        self.create_post(conf, msg, &channel_id, &[])
+    }
+
+    fn post_file(&self, conf: &BotConfig, msg: &BotSlackMessage) -> BotResult<serde_json::Value> {
+        let file = msg.file.as_ref().ok_or(anyhow!("No file to upload"))?;
+        info!("Posting file to Mattermost: {:?}", &msg);
+        let channel_id = msg.channel.clone().map(Ok).unwrap_or_else(|| self.channel_id(conf))?;
+
+        let form = reqwest::blocking::multipart::Form::new()
+            .text("channel_id", channel_id.clone())
+            .part("files", reqwest::blocking::multipart::Part::file(file)?);
+        let uploaded = {
+            let _permit = conf.http_semaphore.acquire();
+            let res = reqwest::blocking::Client::builder().timeout(conf.upload_timeout).build()?
+                .post(format!("{}/api/v4/files", self.url))
+                .bearer_auth(&self.token)
+                .multipart(form)
+                .send()?;
+            check_response(res)?
+        };
+        let file_id = uploaded["file_infos"][0]["id"].as_str()
+            .ok_or(anyhow!("No file ID in Mattermost upload response"))?
+            .to_string();
+        self.create_post(conf, msg, &channel_id, &[file_id])
+    }
+}
+
+/// Post message: bold title and text, cut to Mattermost's limit
+fn message(msg: &BotSlackMessage) -> String {
+    let message = match (&msg.title, &msg.text) {
+        (Some(title), Some(text)) => format!("**{}**\n{}", title, text),
+        (Some(title), None) => format!("**{}**", title),
+        (None, text) => text.clone().unwrap_or_default(),
+    };
+    if message.chars().count() <= MESSAGE_MAX_CHARS {
+        return message;
+    }
+    message.chars().take(MESSAGE_MAX_CHARS - 1).chain(std::iter::once('…')).collect()
+}
+
+/**
+ * Check a Mattermost API response. Rate limits and server errors are
+ * returned as HTTP errors (retried), others with Mattermost's error message.
+ */
+fn check_response(res: reqwest::blocking::Response) -> BotResult<serde_json::Value> {
+    let status = res.status();
+    if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(res.error_for_status().unwrap_err().into());
+    }
+    let body = res.text().unwrap_or_default();
+    let json = serde_json::from_str::<serde_json::Value>(&body).unwrap_or_default();
+    if !status.is_success() {
+        error!("Mattermost error response: {} {}", status, body);
+        let message = json["message"].as_str().map(|s| s.to_string()).unwrap_or_else(|| body.trim().to_string());
+        return Err(BotError::DestinationError(format!("Mattermost: {} ({})", message, status)));
+    }
+    Ok(json)
+}
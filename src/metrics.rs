@@ -0,0 +1,140 @@
+use std::{collections::{BTreeMap, HashMap}, fmt::Write, sync::{Arc, Mutex, atomic::{AtomicU64, Ordering}}, time::Duration};
+
+use crate::control::Toggles;
+
+/// Prefix of all metric names
+const PREFIX: &str = "folder_echo";
+
+/**
+ * Per-section counters for the Prometheus `/metrics` endpoint. They start
+ * from zero when a section's worker is restarted (config reload), which
+ * Prometheus handles as a counter reset.
+ */
+#[derive(Debug, Default)]
+pub struct Counters {
+    posted: AtomicU64,
+    bytes_uploaded: AtomicU64,
+    upload_micros: AtomicU64,       // Sum of the time taken to post files
+    rejected: Mutex<BTreeMap<&'static str, u64>>,  // By error code
+    rate_limited: AtomicU64,        // Times uploads were held back by the limit_uploads_per_* limits
+}
+
+impl Counters {
+    /// Count a posted file
+    pub fn posted(&self, size: u64, took: Duration) {
+        self.posted.fetch_add(1, Ordering::Relaxed);
+        self.bytes_uploaded.fetch_add(size, Ordering::Relaxed);
+        self.upload_micros.fetch_add(took.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Count a rejected file, by `BotError::code()`
+    pub fn rejected(&self, code: &'static str) {
+        *self.rejected.lock().unwrap().entry(code).or_insert(0) += 1;
+    }
+
+    /// Count a hold on uploads by the limit_uploads_per_* limits
+    pub fn rate_limited(&self) {
+        self.rate_limited.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Metric families: name (without prefix), Prometheus type, description
+const FAMILIES: &[(&str, &str, &str)] = &[
+    ("files_posted_total", "counter", "Files posted"),
+    ("files_rejected_total", "counter", "Files rejected, by error code"),
+    ("bytes_uploaded_total", "counter", "Bytes of posted files"),
+    ("upload_duration_seconds", "summary", "Time taken to post files"),
+    ("rate_limited_total", "counter", "Times uploads were held back by the limit_uploads_per_* limits"),
+    ("watcher_overflows_total", "counter", "Times the file watcher dropped events and rescanned the folder"),
+    ("queue_depth", "gauge", "Files waiting to be posted"),
+    ("last_post_timestamp_seconds", "gauge", "Unix time of the last successful post (0 if none)"),
+    ("up", "gauge", "1 if the section's worker is running"),
+];
+
+/// One value of a section's metric
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub family: &'static str,       // Name without prefix, e.g. "files_posted_total"
+    pub suffix: &'static str,       // "_sum" or "_count" for a summary, otherwise empty
+    pub counter: bool,              // Only ever grows (until the section restarts), otherwise a gauge
+    pub section: String,
+    pub labels: Vec<(&'static str, String)>,    // Besides the section
+    pub value: f64,
+}
+
+impl Sample {
+    /// Full metric name, e.g. "folder_echo_upload_duration_seconds_sum"
+    pub fn name(&self) -> String {
+        format!("{}_{}{}", PREFIX, self.family, self.suffix)
+    }
+}
+
+/**
+ * Current metrics of all sections, ordered by family and section.
+ *
+ * @param sections Toggles by section name
+ */
+pub fn collect(sections: &HashMap<String, Arc<Toggles>>) -> Vec<Sample>
+{
+    let mut names: Vec<&String> = sections.keys().collect();
+    names.sort();
+    let get = |n: &AtomicU64| n.load(Ordering::Relaxed) as f64;
+    let mut samples = Vec::new();
+    for &(family, kind, _) in FAMILIES {
+        for section in &names {
+            let t = &sections[*section];
+            let values: Vec<(&'static str, Vec<(&'static str, String)>, f64)> = match family {
+                "files_posted_total" => vec![("", vec![], get(&t.metrics.posted))],
+                "files_rejected_total" => t.metrics.rejected.lock().unwrap().iter()
+                    .map(|(code, n)| ("", vec![("code", code.to_string())], *n as f64))
+                    .collect(),
+                "bytes_uploaded_total" => vec![("", vec![], get(&t.metrics.bytes_uploaded))],
+                "upload_duration_seconds" => vec![
+                    ("_sum", vec![], get(&t.metrics.upload_micros) / 1e6),
+                    ("_count", vec![], get(&t.metrics.posted)),
+                ],
+                "rate_limited_total" => vec![("", vec![], get(&t.metrics.rate_limited))],
+                "watcher_overflows_total" => vec![("", vec![], get(&t.watcher_overflows))],
+                "queue_depth" => vec![("", vec![], get(&t.queue_depth))],
+                "last_post_timestamp_seconds" => vec![("", vec![], get(&t.last_posted))],
+                "up" => vec![("", vec![], if t.stop.load(Ordering::Relaxed) { 0.0 } else { 1.0 })],
+                _ => vec![],
+            };
+            samples.extend(values.into_iter().map(|(suffix, labels, value)| Sample {
+                family,
+                suffix,
+                counter: kind != "gauge",
+                section: section.to_string(),
+                labels,
+                value,
+            }));
+        }
+    }
+    samples
+}
+
+/// Escape a label value for the Prometheus text format
+fn label(value: &str) -> String
+{
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/**
+ * Render the metrics of all sections in the Prometheus text exposition format.
+ *
+ * @param sections Toggles by section name
+ */
+pub fn render(sections: &HashMap<String, Arc<Toggles>>) -> String
+{
+    let samples = collect(sections);
+    let mut out = String::new();
+    for (family, kind, help) in FAMILIES {
+        let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, family, help);
+        let _ = writeln!(out, "# TYPE {}_{} {}", PREFIX, family, kind);
+        for s in samples.iter().filter(|s| s.family == *family) {
+            let labels: String = s.labels.iter().map(|(k, v)| format!(",{}=\"{}\"", k, label(v))).collect();
+            let _ = writeln!(out, "{}{{section=\"{}\"{}}} {}", s.name(), label(&s.section), labels, s.value);
+        }
+    }
+    out
+}
@@ -0,0 +1,179 @@
+use std::{collections::HashMap, net::UdpSocket, time::Duration};
+use log::{info, debug, warn};
+use anyhow::anyhow;
+
+use crate::{BotResult, VERSION, control::{self, SectionToggles}, metrics::{self, Sample}};
+
+/// How often metrics are pushed
+pub const PUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Largest StatsD datagram, to stay under common MTUs
+const STATSD_MAX_PACKET: usize = 1400;
+
+/**
+ * Where to push metrics (`--metrics-push`), for setups that don't scrape
+ * the Prometheus endpoint.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    /// StatsD over UDP, "statsd://host:port", with DogStatsD style tags
+    Statsd(String),
+    /// OpenTelemetry OTLP/HTTP (JSON), "otlp+http(s)://host:port[/path]"
+    Otlp(String),
+}
+
+impl std::str::FromStr for Target {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(addr) = s.strip_prefix("statsd://") {
+            let addr = addr.trim_end_matches('/');
+            return if addr.is_empty() { Err(()) } else { Ok(Target::Statsd(addr.to_string())) };
+        }
+        let url = s.strip_prefix("otlp+").filter(|u| u.starts_with("http://") || u.starts_with("https://")).ok_or(())?;
+        let rest = url.split_once("://").map(|(_, r)| r).unwrap_or_default();
+        match rest.split_once('/') {
+            None | Some((_, "")) => Ok(Target::Otlp(format!("{}/v1/metrics", url.trim_end_matches('/')))),
+            Some(_) => Ok(Target::Otlp(url.to_string())),
+        }
+    }
+}
+
+/**
+ * Push the metrics of all sections (the same series as `/metrics`) every
+ * `PUSH_INTERVAL` in a background thread. StatsD gets counters as
+ * increments since the last push, OTLP as cumulative sums.
+ *
+ * @param target Where to push
+ * @param sections Toggles by section name
+ */
+pub fn start(target: Target, sections: SectionToggles) -> BotResult<()>
+{
+    let socket = match &target {
+        Target::Statsd(addr) => {
+            let socket = UdpSocket::bind(if addr.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" })?;
+            socket.connect(addr).map_err(|e| anyhow!("Invalid StatsD address {:?}: {}", addr, e))?;
+            Some(socket)
+        },
+        Target::Otlp(_) => None,
+    };
+    let client = reqwest::blocking::Client::builder()
+        .timeout(PUSH_INTERVAL)
+        .user_agent(format!("{}/{}", crate::NAME, VERSION))
+        .build()?;
+    info!("Pushing metrics every {:?} to {:?}", PUSH_INTERVAL, target);
+    let started = control::unix_now();
+    std::thread::spawn(move || {
+        let mut sent: HashMap<String, f64> = HashMap::new();    // Last pushed counter values (StatsD)
+        let mut failing = false;
+        loop {
+            std::thread::sleep(PUSH_INTERVAL);
+            let samples = metrics::collect(&sections.lock().unwrap());
+            let res = match (&target, &socket) {
+                (Target::Statsd(_), Some(socket)) => push_statsd(socket, &samples, &mut sent),
+                (Target::Otlp(url), _) => push_otlp(&client, url, &samples, started),
+                _ => Ok(()),
+            };
+            match res {
+                Ok(_) if failing => {
+                    info!("Pushing metrics works again");
+                    failing = false;
+                },
+                Ok(_) => debug!("Pushed {} metric sample(s)", samples.len()),
+                // Warn once per outage, not every interval
+                Err(e) if !failing => {
+                    warn!("Failed to push metrics to {:?}: {:?}", target, e);
+                    failing = true;
+                },
+                Err(e) => debug!("Failed to push metrics: {:?}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// StatsD tag value, without the characters that separate tags
+fn tag(value: &str) -> String
+{
+    value.chars().map(|c| if matches!(c, ',' | '|' | '#' | ':' | '\n') { '_' } else { c }).collect()
+}
+
+fn push_statsd(socket: &UdpSocket, samples: &[Sample], sent: &mut HashMap<String, f64>) -> BotResult<()>
+{
+    let mut lines = Vec::new();
+    for s in samples {
+        let tags: Vec<String> = std::iter::once(format!("section:{}", tag(&s.section)))
+            .chain(s.labels.iter().map(|(k, v)| format!("{}:{}", k, tag(v))))
+            .collect();
+        let line = if s.counter {
+            let key = format!("{}|{}", s.name(), tags.join(","));
+            let last = sent.insert(key, s.value).unwrap_or(0.0);
+            // A smaller value means the section restarted and its counters started over
+            let delta = if s.value >= last { s.value - last } else { s.value };
+            if delta == 0.0 {
+                continue;
+            }
+            format!("{}:{}|c|#{}", s.name(), delta, tags.join(","))
+        } else {
+            format!("{}:{}|g|#{}", s.name(), s.value, tags.join(","))
+        };
+        lines.push(line);
+    }
+
+    let mut packet = String::new();
+    for line in lines {
+        if !packet.is_empty() && packet.len() + 1 + line.len() > STATSD_MAX_PACKET {
+            socket.send(packet.as_bytes())?;
+            packet.clear();
+        }
+        if !packet.is_empty() {
+            packet.push('\n');
+        }
+        packet.push_str(&line);
+    }
+    if !packet.is_empty() {
+        socket.send(packet.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn push_otlp(client: &reqwest::blocking::Client, url: &str, samples: &[Sample], started: u64) -> BotResult<()>
+{
+    let now_nanos = (control::unix_now() * 1_000_000_000).to_string();
+    let start_nanos = (started * 1_000_000_000).to_string();
+    let mut by_name: Vec<(String, bool, Vec<serde_json::Value>)> = Vec::new();
+    for s in samples {
+        let attributes: Vec<serde_json::Value> = std::iter::once(("section", s.section.clone()))
+            .chain(s.labels.iter().cloned())
+            .map(|(k, v)| serde_json::json!({ "key": k, "value": { "stringValue": v } }))
+            .collect();
+        let point = serde_json::json!({
+            "attributes": attributes,
+            "startTimeUnixNano": start_nanos,
+            "timeUnixNano": now_nanos,
+            "asDouble": s.value,
+        });
+        match by_name.iter_mut().find(|(name, _, _)| *name == s.name()) {
+            Some((_, _, points)) => points.push(point),
+            None => by_name.push((s.name(), s.counter, vec![point])),
+        }
+    }
+    let metrics: Vec<serde_json::Value> = by_name.into_iter().map(|(name, counter, points)| {
+        if counter {
+            serde_json::json!({ "name": name, "sum": { "dataPoints": points, "aggregationTemporality": 2, "isMonotonic": true } })
+        } else {
+            serde_json::json!({ "name": name, "gauge": { "dataPoints": points } })
+        }
+    }).collect();
+    let payload = serde_json::json!({
+        "resourceMetrics": [{
+            "resource": { "attributes": [{ "key": "service.name", "value": { "stringValue": crate::NAME } }] },
+            "scopeMetrics": [{ "scope": { "name": crate::NAME, "version": VERSION }, "metrics": metrics }],
+        }],
+    });
+    client.post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(payload.to_string())
+        .send()?
+        .error_for_status()?;
+    Ok(())
+}
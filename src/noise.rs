@@ -0,0 +1,115 @@
+use std::{collections::{HashMap, VecDeque}, sync::Mutex, time::{Duration, Instant}};
+use log::{info, warn};
+
+use crate::{BotConfig, BotResult, BotSlackMessage, check_slack_response, post_message_with_retries, slack_client};
+
+const WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Parent message of an ongoing flood thread
+#[derive(Debug)]
+struct Flood {
+    channel_id: String,
+    ts: String,
+    files: usize,
+}
+
+#[derive(Debug, Default)]
+struct ChannelState {
+    recent: VecDeque<Instant>,      // Post times within WINDOW
+    flood: Option<Flood>,
+}
+
+/**
+ * Noise control: once more than `threshold` files were posted to a channel
+ * within an hour, further files go into a thread under a "High volume"
+ * parent message, until the rate drops below the threshold again.
+ */
+#[derive(Debug)]
+pub struct NoiseControl {
+    threshold: usize,
+    channels: Mutex<HashMap<String, ChannelState>>,
+}
+
+impl NoiseControl {
+    pub fn new(threshold: usize) -> Self {
+        NoiseControl { threshold, channels: Mutex::new(HashMap::new()) }
+    }
+
+    /**
+     * Thread to post the next file in, if the channel is currently flooded.
+     * Posts the parent message when a flood starts.
+     *
+     * @param conf Section configuration
+     * @param channel Channel the file is going to (name or ID)
+     * @return Thread ts, or None to post in the channel normally
+     */
+    pub fn thread_for(&self, conf: &BotConfig, channel: &str) -> BotResult<Option<String>> {
+        let mut channels = self.channels.lock().unwrap();
+        let state = channels.entry(channel.to_string()).or_default();
+        while state.recent.front().map(|t| t.elapsed() > WINDOW).unwrap_or(false) {
+            state.recent.pop_front();
+        }
+        if state.recent.len() < self.threshold {
+            if state.flood.take().is_some() {
+                info!("Volume in {:?} back to normal, posting files in the channel again", channel);
+            }
+            return Ok(None);
+        }
+        if state.flood.is_none() {
+            info!("Over {} files in {:?} within an hour, posting further files in a thread", self.threshold, channel);
+            let res = post_message_with_retries(conf, &BotSlackMessage {
+                text: Some(parent_text(0)),
+                channel: Some(channel.to_string()),
+                ..Default::default()
+            })?;
+            match (res["channel"].as_str(), res["ts"].as_str()) {
+                (Some(channel_id), Some(ts)) => state.flood = Some(Flood {
+                    channel_id: channel_id.to_string(),
+                    ts: ts.to_string(),
+                    files: 0,
+                }),
+                _ => return Ok(None),     // No thread to post in (webhook)
+            }
+        }
+        Ok(state.flood.as_ref().map(|f| f.ts.clone()))
+    }
+
+    /**
+     * Count a file posted to a channel, and update the flood thread's
+     * parent message if it went there.
+     */
+    pub fn record(&self, conf: &BotConfig, channel: &str, thread_ts: Option<&str>) {
+        let mut channels = self.channels.lock().unwrap();
+        let state = channels.entry(channel.to_string()).or_default();
+        state.recent.push_back(Instant::now());
+        if let Some(flood) = state.flood.as_mut().filter(|f| Some(f.ts.as_str()) == thread_ts) {
+            flood.files += 1;
+            if let Err(e) = update_message(conf, &flood.channel_id, &flood.ts, &parent_text(flood.files)) {
+                warn!("Failed to update high volume message in {:?}: {:?}", channel, e);
+            }
+        }
+    }
+}
+
+fn parent_text(files: usize) -> String {
+    match files {
+        0 => "*High volume:* posting new files in this thread until things calm down".to_string(),
+        1 => "*High volume:* 1 file in this thread".to_string(),
+        n => format!("*High volume:* {} files in this thread", n),
+    }
+}
+
+/**
+ * Replace the text of a message with chat.update.
+ */
+fn update_message(conf: &BotConfig, channel_id: &str, ts: &str, text: &str) -> BotResult<()>
+{
+    let _permit = conf.http_semaphore.acquire();
+    let res = slack_client(&conf.extra_headers).build()?
+        .post("https://slack.com/api/chat.update")
+        .form(&[("channel", channel_id), ("ts", ts), ("text", text)])
+        .bearer_auth(conf.slack_token.get())
+        .send()?;
+    check_slack_response(res)?;
+    Ok(())
+}
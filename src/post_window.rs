@@ -0,0 +1,82 @@
+use chrono::{DateTime, Datelike, NaiveTime, Utc, Weekday};
+
+/**
+ * Days and time of day when files may be posted, e.g.
+ * "Mon-Fri 08:00-18:00 Europe/Helsinki". Without a time zone, local time
+ * is used. An end time at or before the start time (e.g. "22:00-06:00")
+ * means the window continues past midnight.
+ */
+#[derive(Debug, Clone)]
+pub struct PostWindow {
+    days: [bool; 7],        // Monday first
+    start: NaiveTime,
+    end: NaiveTime,
+    tz: Option<chrono_tz::Tz>,
+}
+
+impl PostWindow {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        let (days, times, tz) = match parts.as_slice() {
+            [days, times] => (*days, *times, None),
+            [days, times, tz] => (*days, *times, Some(tz.parse::<chrono_tz::Tz>().map_err(|_| format!("unknown time zone {:?}", tz))?)),
+            _ => return Err(format!("expected e.g. \"Mon-Fri 08:00-18:00 Europe/Helsinki\", got {:?}", s)),
+        };
+        let (start, end) = times.split_once('-').ok_or(format!("expected a time range like 08:00-18:00, got {:?}", times))?;
+        let time = |t: &str| NaiveTime::parse_from_str(t, "%H:%M").map_err(|_| format!("invalid time {:?} (expected HH:MM)", t));
+        Ok(PostWindow { days: parse_days(days)?, start: time(start)?, end: time(end)?, tz })
+    }
+
+    /**
+     * Check if files may be posted at the given time.
+     */
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        let (weekday, time) = match self.tz {
+            Some(tz) => { let t = now.with_timezone(&tz); (t.weekday(), t.time()) },
+            None => { let t = now.with_timezone(&chrono::Local); (t.weekday(), t.time()) },
+        };
+        let day = |d: Weekday| self.days[d.num_days_from_monday() as usize];
+        if self.start < self.end {
+            day(weekday) && time >= self.start && time < self.end
+        } else {
+            // Past midnight: the early hours belong to the previous day's window
+            (day(weekday) && time >= self.start) || (day(weekday.pred()) && time < self.end)
+        }
+    }
+
+    /**
+     * When the window opens next (to the minute), for log messages.
+     */
+    pub fn next_open(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        (1..=8 * 24 * 60).map(|m| now + chrono::Duration::minutes(m)).find(|t| self.is_open(*t))
+    }
+}
+
+/**
+ * Parse days like "Mon-Fri", "Sat,Sun", "Fri-Mon" or "*" (every day).
+ */
+fn parse_days(s: &str) -> Result<[bool; 7], String> {
+    let mut days = [false; 7];
+    if s == "*" {
+        return Ok([true; 7]);
+    }
+    let weekday = |d: &str| d.parse::<Weekday>().map_err(|_| format!("invalid weekday {:?}", d));
+    for item in s.split(',').filter(|i| !i.is_empty()) {
+        let (first, last) = match item.split_once('-') {
+            Some((a, b)) => (weekday(a)?, weekday(b)?),
+            None => (weekday(item)?, weekday(item)?),
+        };
+        let mut d = first;
+        loop {
+            days[d.num_days_from_monday() as usize] = true;
+            if d == last {
+                break;
+            }
+            d = d.succ();
+        }
+    }
+    if !days.contains(&true) {
+        return Err(format!("no days in {:?}", s));
+    }
+    Ok(days)
+}
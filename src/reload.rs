@@ -0,0 +1,106 @@
+use std::{path::Path, sync::{Arc, atomic::{AtomicBool, Ordering}}};
+use notify::{Watcher, RecommendedWatcher};
+use log::{debug, info, warn};
+use anyhow::anyhow;
+
+use crate::BotResult;
+
+/**
+ * Set `flag` when the process gets SIGHUP.
+ */
+#[cfg(unix)]
+pub fn on_sighup(flag: Arc<AtomicBool>) -> BotResult<()>
+{
+    signal_hook::flag::register(signal_hook::consts::SIGHUP, flag)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn on_sighup(_flag: Arc<AtomicBool>) -> BotResult<()>
+{
+    Ok(())
+}
+
+/**
+ * Set `flag` when the process gets SIGTERM or SIGINT (Ctrl-C), for a
+ * graceful shutdown. A second signal while shutting down exits right away.
+ */
+pub fn on_terminate(flag: Arc<AtomicBool>) -> BotResult<()>
+{
+    for sig in [signal_hook::consts::SIGTERM, signal_hook::consts::SIGINT] {
+        signal_hook::flag::register_conditional_shutdown(sig, 1, flag.clone())?;
+        signal_hook::flag::register(sig, flag.clone())?;
+    }
+    Ok(())
+}
+
+/**
+ * Set `flag` whenever the config file is written or replaced. The parent
+ * directory is watched, since many editors save by renaming a new file
+ * over the old one.
+ *
+ * @param path Config file
+ * @param flag Flag to set on change
+ */
+pub fn watch_file(path: &Path, flag: Arc<AtomicBool>) -> BotResult<()>
+{
+    let name = path.file_name().ok_or(anyhow!("Invalid config file path: {:?}", path))?.to_os_string();
+    info!("Watching config file for changes: {:?}", path);
+    watch_dir(&parent_dir(path), move |p| p.file_name() == Some(name.as_os_str()), flag)
+}
+
+/**
+ * Set `flag` whenever a file matching one of the config `include` patterns
+ * is added, changed or removed. Only the last path component may contain
+ * wildcards (e.g. every "*.ini" in "/etc/folder-echo/conf.d/").
+ *
+ * @param patterns Include glob patterns
+ * @param flag Flag to set on change
+ */
+pub fn watch_includes(patterns: &[String], flag: Arc<AtomicBool>) -> BotResult<()>
+{
+    for pattern in patterns {
+        let dir = parent_dir(Path::new(pattern));
+        if dir.to_string_lossy().contains(['*', '?', '[']) {
+            warn!("Can't watch include pattern with wildcards in directories, changes need SIGHUP: {:?}", pattern);
+            continue;
+        }
+        let glob = glob::Pattern::new(pattern).map_err(|e| anyhow!("Invalid include pattern {:?}: {}", pattern, e))?;
+        info!("Watching included config files for changes: {:?}", pattern);
+        watch_dir(&dir, move |p| glob.matches_path(p), flag.clone())?;
+    }
+    Ok(())
+}
+
+fn parent_dir(path: &Path) -> std::path::PathBuf
+{
+    match path.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+        _ => Path::new(".").to_path_buf(),
+    }
+}
+
+/**
+ * Watch a directory in a background thread and set `flag` when
+ * a file for which `matches` returns true is written, created or removed.
+ */
+fn watch_dir<F>(dir: &Path, matches: F, flag: Arc<AtomicBool>) -> BotResult<()>
+    where F: Fn(&Path) -> bool + Send + 'static
+{
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = RecommendedWatcher::new(tx, notify::Config::default())?;
+    watcher.watch(dir, notify::RecursiveMode::NonRecursive)?;
+
+    std::thread::spawn(move || {
+        let _watcher = watcher;     // Keep alive while the thread runs
+        for event in rx.into_iter().flatten() {
+            let is_change = matches!(event.kind,
+                notify::EventKind::Create(_) | notify::EventKind::Modify(_) | notify::EventKind::Remove(_));
+            if is_change && event.paths.iter().any(|p| matches(p)) {
+                debug!("Config file changed: {:?}", event);
+                flag.store(true, Ordering::Relaxed);
+            }
+        }
+    });
+    Ok(())
+}
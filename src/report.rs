@@ -0,0 +1,63 @@
+use std::{path::Path, time::SystemTime};
+use log::info;
+
+use crate::{BotResult, RunSummary};
+
+fn timestamp(t: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/**
+ * Write a JSON report of a --once run: every file with its outcome, Slack
+ * permalink or error code, and timings, for orchestration tools. Files
+ * left unposted (--max-runtime, post_window) are listed as "unprocessed".
+ *
+ * @param path Report file (replaced atomically)
+ * @param results (section name, bot thread result) pairs
+ * @param started When the run started
+ */
+pub fn write(path: &Path, results: &[(String, BotResult<RunSummary>)], started: SystemTime) -> BotResult<()>
+{
+    let sections: Vec<serde_json::Value> = results.iter().map(|(section, res)| match res {
+        Ok(s) => {
+            let files = s.files.iter().map(|f| serde_json::json!({
+                    "file": f.path.file_name().unwrap_or_default().to_string_lossy(),
+                    "path": f.path.to_string_lossy(),
+                    "outcome": f.outcome,
+                    "permalink": f.permalink,
+                    "code": f.error.as_ref().map(|(code, _)| code),
+                    "error": f.error.as_ref().map(|(_, e)| e),
+                    "started": timestamp(f.started),
+                    "duration_secs": f.duration.as_secs_f64(),
+                }))
+                .chain(s.unprocessed.iter().map(|p| serde_json::json!({
+                    "file": p.file_name().unwrap_or_default().to_string_lossy(),
+                    "path": p.to_string_lossy(),
+                    "outcome": "unprocessed",
+                })))
+                .collect::<Vec<_>>();
+            serde_json::json!({ "section": section, "ok": s.rejected.is_empty(), "files": files })
+        },
+        Err(e) => serde_json::json!({
+            "section": section,
+            "ok": false,
+            "code": e.code(),
+            "error": e.to_string(),
+            "files": [],
+        }),
+    }).collect();
+    let finished = SystemTime::now();
+    let report = serde_json::json!({
+        "ok": sections.iter().all(|s| s["ok"].as_bool() == Some(true)),
+        "started": timestamp(started),
+        "finished": timestamp(finished),
+        "duration_secs": finished.duration_since(started).unwrap_or_default().as_secs_f64(),
+        "sections": sections,
+    });
+
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, serde_json::to_string_pretty(&report).unwrap_or_default())?;
+    std::fs::rename(&tmp, path)?;
+    info!("Wrote run report to {:?}", path);
+    Ok(())
+}
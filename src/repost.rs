@@ -0,0 +1,82 @@
+use std::{io::Write, path::{Path, PathBuf}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use crate::{BotResult, parse_duration};
+
+/// When a file with the same name may be posted to a destination again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepostPolicy {
+    Always,
+    OncePerDay,         // Not again on the same (local) calendar day
+    NeverRepeat,
+    Window(Duration),   // Not again within this long
+}
+
+impl std::str::FromStr for RepostPolicy {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(RepostPolicy::Always),
+            "once-per-day" => Ok(RepostPolicy::OncePerDay),
+            "never-repeat" => Ok(RepostPolicy::NeverRepeat),
+            d => parse_duration(d).filter(|d| !d.is_zero()).map(RepostPolicy::Window).ok_or(()),
+        }
+    }
+}
+
+impl RepostPolicy {
+    /**
+     * Check if a post is allowed, given when the same file was last posted.
+     */
+    pub fn allows(&self, last: Option<SystemTime>, now: SystemTime) -> bool {
+        let last = match last {
+            Some(t) => t,
+            None => return true,
+        };
+        match self {
+            RepostPolicy::Always => true,
+            RepostPolicy::NeverRepeat => false,
+            RepostPolicy::OncePerDay => {
+                let day = |t: SystemTime| chrono::DateTime::<chrono::Local>::from(t).date_naive();
+                day(last) != day(now)
+            },
+            RepostPolicy::Window(w) => now.duration_since(last).map(|d| d >= *w).unwrap_or(false),
+        }
+    }
+}
+
+/**
+ * When files were last posted to each destination
+ * ("<unix time>\t<destination label>\t<name>" per line), for `repost_policy`.
+ */
+#[derive(Debug, Clone)]
+pub struct RepostLog {
+    path: PathBuf,
+}
+
+impl RepostLog {
+    pub fn new(path: &Path) -> Self {
+        RepostLog { path: path.to_path_buf() }
+    }
+
+    /**
+     * Latest post of a file (by name) to a destination, if any.
+     */
+    pub fn last_posted(&self, label: &str, name: &str) -> Option<SystemTime> {
+        let content = std::fs::read_to_string(&self.path).ok()?;
+        content.lines().rev()
+            .filter_map(|l| {
+                let mut parts = l.splitn(3, '\t');
+                Some((parts.next()?, parts.next()?, parts.next()?))
+            })
+            .find(|(_, l, n)| *l == label && *n == name)
+            .and_then(|(t, _, _)| t.parse::<u64>().ok())
+            .map(|t| UNIX_EPOCH + Duration::from_secs(t))
+    }
+
+    pub fn record(&self, label: &str, name: &str) -> BotResult<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let mut f = std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(f, "{}\t{}\t{}", now, label, name)?;
+        Ok(())
+    }
+}
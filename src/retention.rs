@@ -0,0 +1,86 @@
+use std::{io::Write, path::{Path, PathBuf}, time::{Duration, SystemTime, UNIX_EPOCH}};
+use log::{info, warn};
+
+use crate::BotResult;
+
+/**
+ * Append-only log of files uploaded to Slack ("<unix timestamp> <file id>" per line),
+ * used to delete them from Slack after a retention period.
+ */
+#[derive(Debug, Clone)]
+pub struct UploadLog {
+    path: PathBuf,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+impl UploadLog {
+    pub fn new(path: &Path) -> Self {
+        UploadLog { path: path.to_path_buf() }
+    }
+
+    /**
+     * Record a newly uploaded Slack file.
+     */
+    pub fn record(&self, file_id: &str) -> BotResult<()> {
+        let mut f = std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(f, "{} {}", unix_now(), file_id)?;
+        Ok(())
+    }
+
+    /**
+     * Call `delete` for every recorded file older than `max_age`, and forget
+     * the ones that were deleted successfully. Failed ones are kept for the next round.
+     *
+     * @param max_age Retention period
+     * @param delete Function that deletes a file from Slack by ID
+     * @return Number of deleted files
+     */
+    pub fn expire<F>(&self, max_age: Duration, mut delete: F) -> BotResult<usize>
+        where F: FnMut(&str) -> BotResult<()>
+    {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let cutoff = unix_now().saturating_sub(max_age.as_secs());
+        let mut keep = Vec::new();
+        let mut deleted = 0;
+        for line in content.lines() {
+            let mut parts = line.split_whitespace();
+            let (ts, file_id) = match (parts.next().and_then(|t| t.parse::<u64>().ok()), parts.next()) {
+                (Some(ts), Some(id)) => (ts, id),
+                _ => {
+                    warn!("Ignoring invalid line in {:?}: {:?}", self.path, line);
+                    continue;
+                },
+            };
+            if ts >= cutoff {
+                keep.push(line);
+                continue;
+            }
+            match delete(file_id) {
+                Ok(()) => deleted += 1,
+                Err(e) => {
+                    warn!("Failed to delete expired Slack file {}: {}", file_id, e);
+                    keep.push(line);
+                },
+            }
+        }
+        if deleted > 0 {
+            // Write to a temp file and rename, so a crash doesn't lose the log
+            let tmp = self.path.with_extension("tmp");
+            let mut f = std::fs::File::create(&tmp)?;
+            for line in keep {
+                writeln!(f, "{}", line)?;
+            }
+            drop(f);
+            std::fs::rename(&tmp, &self.path)?;
+            info!("Deleted {} expired file(s) from Slack", deleted);
+        }
+        Ok(deleted)
+    }
+}
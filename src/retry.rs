@@ -0,0 +1,120 @@
+use std::{path::{Path, PathBuf}, time::Duration};
+use log::{info, debug, warn};
+
+use crate::{BotConfig, BotError, BotResult, move_file, sidecar};
+
+/// Error codes that another attempt won't fix
+const PERMANENT_CODES: &[&str] = &["config", "too-large", "blocked-type", "checksum", "path-too-long"];
+
+/**
+ * Automatic retry of rejected files (`retry_rejected_every`,
+ * `retry_rejected_max`), so a transient outage doesn't need manual cleanup.
+ *
+ * The number of rejections and the latest error code of each file are kept
+ * in a hidden state file next to it in rejected/: ".<name>.retry",
+ * contents "<rejections> <error code>".
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct RetryRejected {
+    pub interval: Duration,
+    pub max_attempts: u32,
+}
+
+fn state_file(rejected_path: &Path) -> PathBuf
+{
+    let name = rejected_path.file_name().unwrap_or_default().to_string_lossy();
+    rejected_path.with_file_name(format!(".{}.retry", name))
+}
+
+fn read_state(rejected_path: &Path) -> Option<(u32, String)>
+{
+    let content = std::fs::read_to_string(state_file(rejected_path)).ok()?;
+    let (count, code) = content.trim().split_once(' ')?;
+    Some((count.parse().ok()?, code.to_string()))
+}
+
+/**
+ * Count a rejection of a file that's now in rejected/.
+ *
+ * @return Rejections so far
+ */
+pub fn record_rejection(rejected_path: &Path, err: &BotError) -> BotResult<u32>
+{
+    let count = read_state(rejected_path).map(|(c, _)| c).unwrap_or(0) + 1;
+    std::fs::write(state_file(rejected_path), format!("{} {}", count, err.code()))?;
+    Ok(count)
+}
+
+/**
+ * Forget the rejections of a file that has now been posted.
+ *
+ * @param rejected_path Where the file was in rejected/
+ */
+pub fn clear(rejected_path: &Path)
+{
+    let state = state_file(rejected_path);
+    if state.exists() {
+        if let Err(e) = std::fs::remove_file(&state) {
+            warn!("Failed to remove {:?}: {}", state, e);
+        }
+    }
+}
+
+/**
+ * Move files that are due for another attempt from rejected/ back to the
+ * folder (with their checksum companions and metadata sidecars): those
+ * rejected for a transient reason fewer than `max_attempts` times.
+ *
+ * @return Files to queue again
+ */
+pub fn collect(conf: &BotConfig, retry: &RetryRejected) -> BotResult<Vec<PathBuf>>
+{
+    let mut files = Vec::new();
+    let mut dirs = vec![conf.rejected_dir.clone()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)?.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                if conf.recursive {
+                    dirs.push(entry.path());
+                }
+            } else if !name.ends_with(".sha256") && sidecar::target_of(&entry.path()).is_none() {
+                files.push(entry.path());
+            }
+        }
+    }
+
+    let mut retried = Vec::new();
+    for path in files {
+        let (count, code) = match read_state(&path) {
+            Some(state) => state,
+            None => continue,       // Rejected before retries were on, or put there by hand
+        };
+        if PERMANENT_CODES.contains(&code.as_str()) || count > retry.max_attempts {
+            debug!("Not retrying {:?} ({} rejection(s), last [{}])", path, count, code);
+            continue;
+        }
+        let rel = path.strip_prefix(&conf.rejected_dir).unwrap_or(&path);
+        let target = conf.folder.join(rel);
+        if target.exists() {
+            warn!("Not retrying {:?}, {:?} is in the way", path, target);
+            continue;
+        }
+        info!("Retrying rejected file (attempt {} of {}, last error [{}]): {:?}", count, retry.max_attempts, code, path);
+        if let Some(dir) = target.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        move_file(&path, &target).map_err(|e| BotError::MoveError(path.clone(), target.clone(), e))?;
+        let mut companions: Vec<PathBuf> = vec![path.with_file_name(format!("{}.sha256", path.file_name().unwrap_or_default().to_string_lossy()))];
+        companions.extend(sidecar::find(&path));
+        for companion in companions.into_iter().filter(|c| c.is_file()) {
+            let to = target.with_file_name(companion.file_name().unwrap_or_default());
+            move_file(&companion, &to).map_err(|e| BotError::MoveError(companion.clone(), to, e))?;
+        }
+        retried.push(target);
+    }
+    Ok(retried)
+}
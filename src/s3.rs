@@ -0,0 +1,204 @@
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}, sync::{Arc, Mutex, mpsc::Sender}, time::Duration};
+use log::{info, debug, warn, error};
+use anyhow::anyhow;
+use ::s3::{Bucket, Region, creds::Credentials};
+
+use crate::{BotConfig, BotError, BotResult, control, parse_duration, secrets, source::{self, Source}};
+
+/// Default `s3_poll_interval`
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Object tag for `s3_archive = tag`
+const TAG_KEY: &str = crate::NAME;
+
+/// What to do with an object once its file has been posted or rejected (`s3_archive`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum S3Archive {
+    Move,       // To the "posted/" or "rejected/" prefix under s3_prefix
+    Tag,        // Tag it (slack-app-folder-echo=posted|rejected) and leave it in place
+}
+
+impl std::str::FromStr for S3Archive {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "move" => Ok(S3Archive::Move),
+            "tag" => Ok(S3Archive::Tag),
+            _ => Err(()),
+        }
+    }
+}
+
+/**
+ * S3 bucket (or any S3 compatible storage) polled for new objects
+ * (`s3_bucket`, `s3_prefix`, `s3_poll_interval`). Objects are downloaded
+ * into the section's folder, posted like local files, and then archived
+ * in the bucket (`s3_archive`).
+ */
+pub struct S3Source {
+    bucket: Box<Bucket>,
+    prefix: String,         // Empty, or ends with '/'
+    poll_interval: Duration,
+    archive: S3Archive,
+    seen: Mutex<HashSet<String>>,               // Keys downloaded or found archived, not to fetch again
+    files: Mutex<HashMap<PathBuf, String>>,     // Downloaded file -> its key
+}
+
+impl std::fmt::Debug for S3Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "S3Source(s3://{}/{}, {:?}, <redacted>)", self.bucket.name(), self.prefix, self.archive)
+    }
+}
+
+impl S3Source {
+    /**
+     * S3 source from a config section.
+     *
+     * @return None if the section has no s3_bucket
+     */
+    pub fn from_config(section: &ini::Properties) -> BotResult<Option<Self>> {
+        let get = |key: &str| section.get(key).map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let name = match get("s3_bucket") {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let region_name = get("s3_region").unwrap_or_else(|| "us-east-1".to_string());
+        let region = match get("s3_endpoint") {
+            Some(endpoint) => Region::Custom { region: region_name, endpoint },
+            None => region_name.parse().map_err(|e| BotError::InvalidConfig(format!("Invalid s3_region: {}", e)))?,
+        };
+        // Without keys, the usual AWS environment variables / profile / instance role
+        let credentials = match (get("s3_access_key"), get("s3_secret_key")) {
+            (Some(access), Some(secret)) => Credentials::new(
+                Some(&secrets::interpolate(&access)?.0), Some(&secrets::interpolate(&secret)?.0), None, None, None),
+            (None, None) => Credentials::default(),
+            _ => return Err(BotError::InvalidConfig("s3_access_key and s3_secret_key go together".to_string())),
+        }.map_err(|e| BotError::InvalidConfig(format!("No S3 credentials: {}", e)))?;
+        let mut bucket = Bucket::new(&name, region, credentials)
+            .map_err(|e| BotError::InvalidConfig(format!("Invalid s3_bucket: {}", e)))?;
+        if section.contains_key("s3_endpoint") {
+            bucket = bucket.with_path_style();      // MinIO and most other S3 compatible servers
+        }
+        let prefix = match get("s3_prefix") {
+            Some(p) => format!("{}/", p.trim_matches('/')),
+            None => String::new(),
+        };
+        let poll_interval = match get("s3_poll_interval") {
+            Some(s) => parse_duration(&s).ok_or(BotError::InvalidConfig(format!("Invalid s3_poll_interval: {:?}", s)))?,
+            None => DEFAULT_POLL_INTERVAL,
+        };
+        let archive = match get("s3_archive") {
+            Some(s) => s.parse().map_err(|_| BotError::InvalidConfig(format!("Invalid s3_archive (expected move or tag): {:?}", s)))?,
+            None => S3Archive::Move,
+        };
+        Ok(Some(S3Source { bucket, prefix, poll_interval, archive, seen: Mutex::new(HashSet::new()), files: Mutex::new(HashMap::new()) }))
+    }
+
+    /**
+     * Download objects that haven't been fetched or archived yet into the folder.
+     * Only objects directly under the prefix are listed, not the archive prefixes.
+     *
+     * @return Downloaded files
+     */
+    fn fetch_new(&self, conf: &BotConfig) -> BotResult<Vec<PathBuf>> {
+        let listing = self.bucket.list(self.prefix.clone(), Some("/".to_string()))
+            .map_err(|e| anyhow!("Failed to list s3://{}/{}: {}", self.bucket.name(), self.prefix, e))?;
+        let mut paths = Vec::new();
+        for obj in listing.into_iter().flat_map(|r| r.contents) {
+            if obj.key.ends_with('/') || self.seen.lock().unwrap().contains(&obj.key) {
+                continue;
+            }
+            self.seen.lock().unwrap().insert(obj.key.clone());
+            if self.archive == S3Archive::Tag && self.is_tagged(&obj.key) {
+                continue;
+            }
+            let name = obj.key.rsplit('/').next().unwrap_or_default();
+            if name.starts_with('.') {
+                debug!("Skipping hidden object: {:?}", obj.key);
+                continue;
+            }
+            let path = conf.folder.join(name);
+            if !conf.file_filter.accepts(&path) {
+                debug!("Skipping object not matching include / exclude: {:?}", obj.key);
+                continue;
+            }
+            if path.exists() {
+                // Downloaded before a restart but not posted yet (see QUEUE_FILE); the queue skips it if it's gone
+                info!("Already downloaded, not fetching again: {:?}", path);
+                self.files.lock().unwrap().insert(path.clone(), obj.key);
+                paths.push(path);
+                continue;
+            }
+            // Download under a hidden name, so a half-written file is never posted
+            let part = conf.folder.join(format!(".{}.part", name));
+            info!("Downloading s3://{}/{} ({} bytes)", self.bucket.name(), obj.key, obj.size);
+            let res = std::fs::File::create(&part).map_err(BotError::from).and_then(|mut out| {
+                let status = self.bucket.get_object_to_writer(&obj.key, &mut out)
+                    .map_err(|e| anyhow!("Failed to download {:?}: {}", obj.key, e))?;
+                if status != 200 {
+                    return Err(anyhow!("Failed to download {:?}: HTTP {}", obj.key, status).into());
+                }
+                Ok(std::fs::rename(&part, &path)?)
+            });
+            if let Err(e) = res {
+                error!("{:?}", e);
+                let _ = std::fs::remove_file(&part);
+                self.seen.lock().unwrap().remove(&obj.key);     // Try again on the next round
+                continue;
+            }
+            self.files.lock().unwrap().insert(path.clone(), obj.key);
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    fn is_tagged(&self, key: &str) -> bool {
+        match self.bucket.get_object_tagging(key) {
+            Ok((tags, _)) => tags.iter().any(|t| t.key() == TAG_KEY),
+            Err(e) => {
+                warn!("Failed to get tags of {:?}, assuming it's new: {}", key, e);
+                false
+            },
+        }
+    }
+
+    /// Archive the object a posted or rejected file came from
+    fn archive(&self, path: &Path, outcome: &str) -> BotResult<()> {
+        let key = match self.files.lock().unwrap().remove(path) {
+            Some(key) => key,
+            None => return Ok(()),      // Not downloaded by us (e.g. --from-archive)
+        };
+        let res = match self.archive {
+            S3Archive::Move => {
+                let to = format!("{}{}/{}", self.prefix, outcome, &key[self.prefix.len()..]);
+                info!("Moving s3://{}/{} to {}", self.bucket.name(), key, to);
+                self.bucket.copy_object_internal(&key, &to)
+                    .and_then(|_| self.bucket.delete_object(&key).map(|_| ()))
+            },
+            S3Archive::Tag => {
+                info!("Tagging s3://{}/{} as {}", self.bucket.name(), key, outcome);
+                self.bucket.put_object_tagging(&key, &[(TAG_KEY, outcome)]).map(|_| ())
+            },
+        };
+        res.map_err(|e| anyhow!("Failed to archive {:?} in S3: {}", key, e).into())
+    }
+}
+
+impl Source for S3Source {
+    fn scan(&self, conf: &BotConfig) -> BotResult<Vec<PathBuf>> {
+        self.fetch_new(conf)
+    }
+
+    fn watch(&self, conf: &BotConfig, paths_tx: Sender<PathBuf>, toggles: Arc<control::Toggles>) -> BotResult<()> {
+        info!("Polling s3://{}/{} every {:?}", self.bucket.name(), self.prefix, self.poll_interval);
+        source::poll(self.poll_interval, paths_tx, toggles, || self.fetch_new(conf))
+    }
+
+    fn posted(&self, _conf: &BotConfig, path: &Path) -> BotResult<()> {
+        self.archive(path, "posted")
+    }
+
+    fn rejected(&self, _conf: &BotConfig, path: &Path) -> BotResult<()> {
+        self.archive(path, "rejected")
+    }
+}
@@ -0,0 +1,217 @@
+use std::{path::{Path, PathBuf}, sync::{Arc, RwLock}};
+use log::info;
+use anyhow::anyhow;
+
+use crate::{BotError, BotResult};
+
+/// Where a token comes from
+#[derive(Clone, PartialEq, Eq)]
+enum TokenSource {
+    Inline,             // Written in the config file
+    File(PathBuf),      // slack_token_file
+    Command(String),    // slack_token_cmd, e.g. a secret manager CLI
+}
+
+/**
+ * A Slack token that can be re-read from its source (file or command)
+ * after it has been rotated. Clones share the current value.
+ */
+#[derive(Clone)]
+pub struct Token {
+    source: TokenSource,
+    value: Arc<RwLock<String>>,
+}
+
+impl std::fmt::Debug for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let source = match &self.source {
+            TokenSource::Inline => "inline".to_string(),
+            TokenSource::File(p) => format!("file {:?}", p),
+            TokenSource::Command(c) => format!("command {:?}", c),
+        };
+        write!(f, "Token({}, <redacted>)", source)
+    }
+}
+
+impl Token {
+    pub fn inline(value: &str) -> Self {
+        Token { source: TokenSource::Inline, value: Arc::new(RwLock::new(value.trim().to_string())) }
+    }
+
+    /**
+     * Token from the `slack_token`, `slack_token_enc`, `slack_token_file` or
+     * `slack_token_cmd` key of a config section (in that order of preference).
+     * `slack_token` can use `${env:NAME}` / `${file:/path}` (see `interpolate()`).
+     *
+     * @return None if the section has none of them
+     */
+    pub fn from_config(section: &ini::Properties) -> BotResult<Option<Self>> {
+        let source = if let Some(t) = section.get("slack_token") {
+            return Ok(Some(Token::inline(&interpolate(t)?.0)));
+        } else if let Some(enc) = section.get("slack_token_enc") {
+            let key_file = section.get("secrets_key_file")
+                .ok_or(BotError::InvalidConfig("slack_token_enc needs secrets_key_file".to_string()))?;
+            return Ok(Some(Token::inline(&decrypt(enc, Path::new(key_file.trim()))?)));
+        } else if let Some(path) = section.get("slack_token_file") {
+            TokenSource::File(PathBuf::from(path.trim()))
+        } else if let Some(cmd) = section.get("slack_token_cmd") {
+            TokenSource::Command(cmd.trim().to_string())
+        } else {
+            return Ok(None);
+        };
+        let value = read(&source)?;
+        Ok(Some(Token { source, value: Arc::new(RwLock::new(value)) }))
+    }
+
+    /**
+     * Token of an `extra_destinations` entry: "file:/path" and "cmd:<command>"
+     * are re-read after rotation like `slack_token_file` / `slack_token_cmd`,
+     * "enc:<base64>" is decrypted with the section's `secrets_key_file`, and
+     * anything else is the token itself (with `${env:NAME}` / `${file:/path}`).
+     */
+    pub fn from_spec(spec: &str, section: &ini::Properties) -> BotResult<Self> {
+        let source = match spec.trim().split_once(':') {
+            Some(("file", path)) => TokenSource::File(PathBuf::from(path.trim())),
+            Some(("cmd", cmd)) => TokenSource::Command(cmd.trim().to_string()),
+            Some(("enc", enc)) => {
+                let key_file = section.get("secrets_key_file")
+                    .ok_or(BotError::InvalidConfig("enc: tokens in extra_destinations need secrets_key_file".to_string()))?;
+                return Ok(Token::inline(&decrypt(enc, Path::new(key_file.trim()))?));
+            },
+            _ => return Ok(Token::inline(&interpolate(spec)?.0)),
+        };
+        let value = read(&source)?;
+        Ok(Token { source, value: Arc::new(RwLock::new(value)) })
+    }
+
+    /// Current value
+    pub fn get(&self) -> String {
+        self.value.read().unwrap().clone()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.value.read().unwrap().is_empty()
+    }
+
+    /**
+     * Re-read the token from its source (after an auth failure).
+     *
+     * @return true if the value changed
+     */
+    pub fn refresh(&self) -> BotResult<bool> {
+        if self.source == TokenSource::Inline {
+            return Ok(false);
+        }
+        let new_value = read(&self.source)?;
+        let mut value = self.value.write().unwrap();
+        if *value == new_value {
+            return Ok(false);
+        }
+        info!("Slack token from {:?} has changed, using the new one", self);
+        *value = new_value;
+        Ok(true)
+    }
+}
+
+/**
+ * Decrypt a base64-encoded age ciphertext, e.g. made with
+ * `echo -n xoxb-... | age -r age1... | base64 -w0`.
+ *
+ * @param encrypted Base64 ciphertext from the config
+ * @param key_file age identity file (`age-keygen` output, "AGE-SECRET-KEY-1..." lines)
+ */
+#[cfg(feature = "encrypted-secrets")]
+fn decrypt(encrypted: &str, key_file: &Path) -> BotResult<String> {
+    use std::{io::Read, str::FromStr};
+    use base64::Engine;
+    let keys = std::fs::read_to_string(key_file)
+        .map_err(|e| BotError::InvalidConfig(format!("Failed to read secrets_key_file {:?}: {}", key_file, e)))?;
+    let identities = keys.lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| age::x25519::Identity::from_str(l)
+            .map_err(|e| BotError::InvalidConfig(format!("Invalid key in secrets_key_file {:?}: {}", key_file, e))))
+        .collect::<BotResult<Vec<_>>>()?;
+    let ciphertext = base64::engine::general_purpose::STANDARD.decode(encrypted.trim())
+        .map_err(|e| BotError::InvalidConfig(format!("Invalid base64 in encrypted value: {}", e)))?;
+    let decryptor = match age::Decryptor::new(&ciphertext[..]) {
+        Ok(age::Decryptor::Recipients(d)) => d,
+        Ok(_) => return Err(BotError::InvalidConfig("Passphrase-encrypted values aren't supported, encrypt to a recipient (age1...)".to_string())),
+        Err(e) => return Err(BotError::InvalidConfig(format!("Invalid encrypted value: {}", e))),
+    };
+    let mut reader = decryptor.decrypt(identities.iter().map(|i| i as &dyn age::Identity))
+        .map_err(|e| BotError::InvalidConfig(format!("Failed to decrypt with {:?}: {}", key_file, e)))?;
+    let mut value = String::new();
+    reader.read_to_string(&mut value)
+        .map_err(|e| BotError::InvalidConfig(format!("Failed to decrypt with {:?}: {}", key_file, e)))?;
+    Ok(value.trim().to_string())
+}
+
+#[cfg(not(feature = "encrypted-secrets"))]
+fn decrypt(_encrypted: &str, _key_file: &Path) -> BotResult<String> {
+    Err(BotError::InvalidConfig("slack_token_enc needs the \"encrypted-secrets\" cargo feature".to_string()))
+}
+
+/**
+ * Replace `${env:NAME}` with the value of an environment variable and
+ * `${file:/path}` with the (trimmed) contents of a file, e.g. for secrets
+ * in `extra_headers`.
+ *
+ * @return Interpolated value, and whether anything was replaced
+ */
+pub fn interpolate(value: &str) -> BotResult<(String, bool)> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    let mut replaced = false;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find('}')
+            .ok_or(BotError::InvalidConfig(format!("Unterminated ${{...}} in {:?}", value)))? + start;
+        let var = &rest[start + 2..end];
+        let val = match var.split_once(':') {
+            Some(("env", name)) => std::env::var(name.trim())
+                .map_err(|_| BotError::InvalidConfig(format!("Environment variable {:?} is not set", name.trim())))?,
+            Some(("file", path)) => std::fs::read_to_string(path.trim())
+                .map_err(|e| BotError::InvalidConfig(format!("Failed to read {:?}: {}", path.trim(), e)))?
+                .trim().to_string(),
+            _ => return Err(BotError::InvalidConfig(format!("Unknown ${{{}}} (expected ${{env:NAME}} or ${{file:/path}})", var))),
+        };
+        out.push_str(&val);
+        replaced = true;
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok((out, replaced))
+}
+
+fn read(source: &TokenSource) -> BotResult<String> {
+    let value = match source {
+        TokenSource::Inline => return Err(anyhow!("Inline token can't be re-read").into()),
+        TokenSource::File(path) => std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read slack_token_file {:?}: {}", path, e))?,
+        TokenSource::Command(cmd) => {
+            let mut parts = cmd.split_whitespace();
+            let program = parts.next().ok_or(anyhow!("Empty slack_token_cmd"))?;
+            let output = std::process::Command::new(program).args(parts).output()
+                .map_err(|e| anyhow!("Failed to run slack_token_cmd {:?}: {}", cmd, e))?;
+            if !output.status.success() {
+                return Err(anyhow!("slack_token_cmd {:?} failed ({}): {}", cmd, output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()).into());
+            }
+            String::from_utf8(output.stdout).map_err(|_| anyhow!("slack_token_cmd {:?} printed invalid UTF-8", cmd))?
+        },
+    };
+    let value = value.trim().to_string();
+    if value.is_empty() {
+        return Err(anyhow!("Empty Slack token from {:?}", source_name(source)).into());
+    }
+    Ok(value)
+}
+
+fn source_name(source: &TokenSource) -> String {
+    match source {
+        TokenSource::Inline => "config".to_string(),
+        TokenSource::File(p) => p.to_string_lossy().to_string(),
+        TokenSource::Command(c) => c.clone(),
+    }
+}
@@ -0,0 +1,97 @@
+use std::{cell::Cell, sync::{Condvar, Mutex}};
+
+thread_local! {
+    /// Whether requests made by this thread go ahead of normal ones
+    static HIGH_PRIORITY: Cell<bool> = const { Cell::new(false) };
+}
+
+#[derive(Debug)]
+struct State {
+    limit: usize,
+    in_use: usize,
+    high_waiting: usize,    // High priority threads blocked in acquire()
+}
+
+/**
+ * Simple counting semaphore for limiting concurrent work across threads.
+ * Threads inside a `high_priority()` scope get permits before normal ones.
+ */
+#[derive(Debug)]
+pub struct Semaphore {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+/**
+ * Permit from `Semaphore::acquire()`, released when dropped.
+ */
+pub struct SemaphoreGuard<'a> {
+    sem: &'a Semaphore,
+}
+
+/**
+ * Priority set by `high_priority()`, restored when dropped.
+ */
+pub struct PriorityGuard {
+    previous: bool,
+}
+
+/**
+ * Make the current thread's semaphore waits high (or normal) priority
+ * until the returned guard is dropped, e.g. while posting an urgent file.
+ */
+pub fn high_priority(high: bool) -> PriorityGuard {
+    PriorityGuard { previous: HIGH_PRIORITY.with(|p| p.replace(high)) }
+}
+
+impl Drop for PriorityGuard {
+    fn drop(&mut self) {
+        HIGH_PRIORITY.with(|p| p.set(self.previous));
+    }
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        Semaphore { state: Mutex::new(State { limit: permits, in_use: 0, high_waiting: 0 }), cond: Condvar::new() }
+    }
+
+    /**
+     * Change the number of permits, e.g. after a config reload. Permits
+     * already taken over a smaller limit are kept until released.
+     */
+    pub fn set_permits(&self, permits: usize) {
+        self.state.lock().unwrap().limit = permits;
+        self.cond.notify_all();
+    }
+
+    /**
+     * Block until a permit is available and take it. Normal priority
+     * threads also wait while any high priority thread is waiting.
+     */
+    pub fn acquire(&self) -> SemaphoreGuard<'_> {
+        let mut state = self.state.lock().unwrap();
+        if HIGH_PRIORITY.with(|p| p.get()) {
+            state.high_waiting += 1;
+            while state.in_use >= state.limit {
+                state = self.cond.wait(state).unwrap();
+            }
+            state.high_waiting -= 1;
+            if state.high_waiting == 0 {
+                self.cond.notify_all();     // Normal priority waiters may go ahead if permits are left
+            }
+        } else {
+            while state.in_use >= state.limit || state.high_waiting > 0 {
+                state = self.cond.wait(state).unwrap();
+            }
+        }
+        state.in_use += 1;
+        SemaphoreGuard { sem: self }
+    }
+}
+
+impl Drop for SemaphoreGuard<'_> {
+    fn drop(&mut self) {
+        self.sem.state.lock().unwrap().in_use -= 1;
+        self.sem.cond.notify_all();     // Waiters have different conditions, let them all re-check
+    }
+}
@@ -0,0 +1,197 @@
+use std::{path::Path, sync::atomic::{AtomicBool, Ordering}};
+
+use crate::BotResult;
+
+/// Set when running under the Windows service control manager
+static RUNNING: AtomicBool = AtomicBool::new(false);
+/// Set when the service control manager asks the service to stop
+static STOP: AtomicBool = AtomicBool::new(false);
+
+/// Running as a Windows service (`--service=run`, started by the service control manager)?
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::Relaxed)
+}
+
+/// Has the service been asked to stop (or the system is shutting down)?
+pub fn stop_requested() -> bool {
+    STOP.load(Ordering::Relaxed)
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::{ffi::OsString, path::Path, sync::atomic::Ordering, time::Duration};
+    use anyhow::anyhow;
+    use log::{info, error};
+    use windows_service::{
+        define_windows_service, service_control_handler, service_dispatcher,
+        service::{ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+            ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType},
+        service_control_handler::ServiceControlHandlerResult,
+        service_manager::{ServiceManager, ServiceManagerAccess},
+    };
+
+    use crate::{BotResult, NAME};
+    use super::{RUNNING, STOP};
+
+    define_windows_service!(ffi_service_main, service_main);
+
+    fn status(state: ServiceState, exit_code: u32) -> ServiceStatus {
+        ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted: if state == ServiceState::Running { ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN } else { ServiceControlAccept::empty() },
+            exit_code: ServiceExitCode::ServiceSpecific(exit_code),
+            checkpoint: 0,
+            wait_hint: if state == ServiceState::StopPending { Duration::from_secs(60) } else { Duration::default() },
+            process_id: None,
+        }
+    }
+
+    fn service_main(_args: Vec<OsString>) {
+        let handler = |event| match event {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                STOP.store(true, Ordering::Relaxed);
+                ServiceControlHandlerResult::NoError
+            },
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        };
+        let handle = match service_control_handler::register(NAME, handler) {
+            Ok(h) => h,
+            Err(e) => {
+                error!("Failed to register service control handler: {}", e);
+                return;
+            },
+        };
+        let _ = handle.set_service_status(status(ServiceState::Running, 0));
+        // The same code path as a foreground daemon, with the arguments the service was registered with
+        let code = match crate::run() {
+            Ok(()) => 0,
+            Err(e) => {
+                error!("Service failed: {:?}", e);
+                1
+            },
+        };
+        let _ = handle.set_service_status(status(ServiceState::Stopped, code));
+    }
+
+    pub fn dispatch() -> BotResult<()> {
+        RUNNING.store(true, Ordering::Relaxed);
+        service_dispatcher::start(NAME, ffi_service_main)
+            .map_err(|e| anyhow!("Failed to start as a Windows service (run it with --service=register first): {}", e))?;
+        Ok(())
+    }
+
+    pub fn register(config_file: &Path, debug: bool) -> BotResult<()> {
+        let config_file = config_file.canonicalize()
+            .map_err(|e| anyhow!("Config file {:?} not found: {}", config_file, e))?;
+        let mut launch_arguments = vec![OsString::from("--service=run")];
+        if debug {
+            launch_arguments.push("--debug".into());
+        }
+        launch_arguments.push(config_file.into_os_string());
+        let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE)
+            .map_err(|e| anyhow!("Can't open the service manager (run as administrator): {}", e))?;
+        let info = ServiceInfo {
+            name: NAME.into(),
+            display_name: "Folder Echo Slack app".into(),
+            service_type: ServiceType::OWN_PROCESS,
+            start_type: ServiceStartType::AutoStart,
+            error_control: ServiceErrorControl::Normal,
+            executable_path: std::env::current_exe()?,
+            launch_arguments,
+            dependencies: vec![],
+            account_name: None,     // LocalSystem
+            account_password: None,
+        };
+        let service = manager.create_service(&info, ServiceAccess::CHANGE_CONFIG)
+            .map_err(|e| anyhow!("Failed to register service: {}", e))?;
+        service.set_description("Posts new files from folders to Slack channels")
+            .map_err(|e| anyhow!("Failed to set service description: {}", e))?;
+        eventlog::register(NAME).map_err(|e| anyhow!("Failed to register event log source: {}", e))?;
+        info!("Registered service {:?}, start it with: sc start {}", NAME, NAME);
+        Ok(())
+    }
+
+    pub fn delete() -> BotResult<()> {
+        let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+            .map_err(|e| anyhow!("Can't open the service manager (run as administrator): {}", e))?;
+        let service = manager.open_service(NAME, ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE)
+            .map_err(|e| anyhow!("Service {:?} not found: {}", NAME, e))?;
+        if service.query_status().map(|s| s.current_state != ServiceState::Stopped).unwrap_or(false) {
+            info!("Stopping service {:?}", NAME);
+            let _ = service.stop();
+        }
+        service.delete().map_err(|e| anyhow!("Failed to delete service: {}", e))?;
+        if let Err(e) = eventlog::deregister(NAME) {
+            log::warn!("Failed to remove event log source: {}", e);
+        }
+        info!("Deleted service {:?} (it's removed once stopped)", NAME);
+        Ok(())
+    }
+
+    pub fn init_log() -> BotResult<()> {
+        let logger = eventlog::EventLog::new(NAME, log::Level::Trace)
+            .map_err(|e| anyhow!("Failed to log to the Windows event log: {}", e))?;
+        crate::logging::install(Box::new(logger))
+    }
+}
+
+#[cfg(not(windows))]
+mod imp {
+    use std::path::Path;
+    use anyhow::anyhow;
+
+    use crate::BotResult;
+
+    fn unsupported() -> BotResult<()> {
+        Err(anyhow!("--service is only supported on Windows").into())
+    }
+
+    pub fn dispatch() -> BotResult<()> {
+        unsupported()
+    }
+
+    pub fn register(_config_file: &Path, _debug: bool) -> BotResult<()> {
+        unsupported()
+    }
+
+    pub fn delete() -> BotResult<()> {
+        unsupported()
+    }
+
+    pub fn init_log() -> BotResult<()> {
+        unsupported()
+    }
+}
+
+/**
+ * Run as a Windows service (`--service=run`): hand the process over to the
+ * service control manager, which calls back to `run()` in service mode.
+ * Blocks until the service stops.
+ */
+pub fn dispatch() -> BotResult<()> {
+    imp::dispatch()
+}
+
+/**
+ * Register the service (`--service=register`) to start at boot, as
+ * LocalSystem, with the given config file. Also registers the event log source.
+ */
+pub fn register(config_file: &Path, debug: bool) -> BotResult<()> {
+    imp::register(config_file, debug)
+}
+
+/**
+ * Stop and delete the service (`--service=delete`).
+ */
+pub fn delete() -> BotResult<()> {
+    imp::delete()
+}
+
+/**
+ * Send the log to the Windows event log (as the service runs without a console).
+ */
+pub fn init_log() -> BotResult<()> {
+    imp::init_log()
+}
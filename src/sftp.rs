@@ -0,0 +1,252 @@
+use std::{collections::HashMap, net::TcpStream, path::{Path, PathBuf}, sync::{Arc, Mutex, mpsc::Sender}, time::Duration};
+use log::{info, debug};
+use anyhow::anyhow;
+use ssh2::{CheckResult, KnownHostFileKind, Session, Sftp};
+
+use crate::{BotConfig, BotError, BotResult, control, parse_duration, secrets, source::{self, Source}};
+
+/// Default `sftp_poll_interval`
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Network timeout for the SSH connection
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+enum Auth {
+    Password(String),
+    KeyFile(PathBuf, Option<String>),   // Private key, passphrase
+    Agent,
+}
+
+/**
+ * Remote directory on an SFTP server (`sftp_url`), listed every
+ * `sftp_poll_interval`. New files are downloaded into the section's folder
+ * once their size stays the same between two listings, and moved to the
+ * remote `posted/` or `rejected/` subdirectory after processing, so the
+ * daemon doesn't have to run on the machine receiving the files.
+ */
+pub struct SftpSource {
+    host: String,
+    port: u16,
+    user: String,
+    dir: PathBuf,
+    auth: Auth,
+    known_hosts: PathBuf,
+    poll_interval: Duration,
+    conn: Mutex<Option<(Session, Sftp)>>,       // Reconnected after errors
+    sizes: Mutex<HashMap<String, u64>>,         // Sizes in the last listing, to tell when a file is complete
+    files: Mutex<HashMap<PathBuf, String>>,     // Downloaded file -> its remote name
+}
+
+impl std::fmt::Debug for SftpSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SftpSource(sftp://{}@{}:{}{}, <redacted>)", self.user, self.host, self.port, self.dir.display())
+    }
+}
+
+impl SftpSource {
+    /**
+     * SFTP source from a config section.
+     *
+     * @return None if the section has no sftp_url
+     */
+    pub fn from_config(section: &ini::Properties) -> BotResult<Option<Self>> {
+        let get = |key: &str| section.get(key).map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let url = match get("sftp_url") {
+            Some(url) => reqwest::Url::parse(&url).map_err(|e| BotError::InvalidConfig(format!("Invalid sftp_url: {}", e)))?,
+            None => return Ok(None),
+        };
+        if url.scheme() != "sftp" || url.password().is_some() {
+            return Err(BotError::InvalidConfig("sftp_url must be like sftp://user@host/path (password in sftp_password)".to_string()));
+        }
+        let host = url.host_str().ok_or(BotError::InvalidConfig("No host in sftp_url".to_string()))?.to_string();
+        if url.username().is_empty() {
+            return Err(BotError::InvalidConfig("No user in sftp_url".to_string()));
+        }
+        let auth = match (get("sftp_password"), get("sftp_key_file")) {
+            (Some(_), Some(_)) => return Err(BotError::InvalidConfig("Use either sftp_password or sftp_key_file, not both".to_string())),
+            (Some(password), None) => Auth::Password(secrets::interpolate(&password)?.0),
+            (None, Some(key)) => Auth::KeyFile(PathBuf::from(key),
+                get("sftp_key_passphrase").map(|p| secrets::interpolate(&p)).transpose()?.map(|(p, _)| p)),
+            (None, None) => Auth::Agent,
+        };
+        let known_hosts = match get("sftp_known_hosts") {
+            Some(path) => PathBuf::from(path),
+            None => PathBuf::from(std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")).unwrap_or_default())
+                .join(".ssh").join("known_hosts"),
+        };
+        let poll_interval = match get("sftp_poll_interval") {
+            Some(s) => parse_duration(&s).ok_or(BotError::InvalidConfig(format!("Invalid sftp_poll_interval: {:?}", s)))?,
+            None => DEFAULT_POLL_INTERVAL,
+        };
+        Ok(Some(SftpSource {
+            host,
+            port: url.port().unwrap_or(22),
+            user: url.username().to_string(),
+            dir: PathBuf::from(url.path()),
+            auth,
+            known_hosts,
+            poll_interval,
+            conn: Mutex::new(None),
+            sizes: Mutex::new(HashMap::new()),
+            files: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// Connect, check the host key against `sftp_known_hosts` and log in
+    fn connect(&self) -> BotResult<(Session, Sftp)> {
+        debug!("Connecting to {}:{}", self.host, self.port);
+        let tcp = TcpStream::connect((self.host.as_str(), self.port))?;
+        tcp.set_read_timeout(Some(TIMEOUT))?;
+        tcp.set_write_timeout(Some(TIMEOUT))?;
+        let mut session = Session::new().map_err(ssh_err)?;
+        session.set_tcp_stream(tcp);
+        session.set_timeout(TIMEOUT.as_millis() as u32);
+        session.handshake().map_err(ssh_err)?;
+
+        let mut known_hosts = session.known_hosts().map_err(ssh_err)?;
+        known_hosts.read_file(&self.known_hosts, KnownHostFileKind::OpenSSH)
+            .map_err(|e| anyhow!("Failed to read sftp_known_hosts {:?}: {}", self.known_hosts, e))?;
+        let (key, _) = session.host_key().ok_or(anyhow!("SFTP server sent no host key"))?;
+        match known_hosts.check_port(&self.host, self.port, key) {
+            CheckResult::Match => {},
+            CheckResult::Mismatch => return Err(anyhow!("Host key of {} doesn't match {:?}, refusing to connect", self.host, self.known_hosts).into()),
+            _ => return Err(anyhow!("{} isn't in {:?} (add it with ssh-keyscan)", self.host, self.known_hosts).into()),
+        }
+
+        match &self.auth {
+            Auth::Password(password) => session.userauth_password(&self.user, password),
+            Auth::KeyFile(key, passphrase) => session.userauth_pubkey_file(&self.user, None, key, passphrase.as_deref()),
+            Auth::Agent => session.userauth_agent(&self.user),
+        }.map_err(|e| anyhow!("SFTP login as {} failed: {}", self.user, e))?;
+        let sftp = session.sftp().map_err(ssh_err)?;
+        info!("Connected to sftp://{}@{}:{}", self.user, self.host, self.port);
+        Ok((session, sftp))
+    }
+
+    /**
+     * Run `f` with the SFTP connection, connecting first if needed.
+     * The connection is dropped on errors, to reconnect on the next call.
+     */
+    fn with_sftp<T>(&self, f: impl FnOnce(&Sftp) -> BotResult<T>) -> BotResult<T> {
+        let mut conn = self.conn.lock().unwrap();
+        if conn.is_none() {
+            *conn = Some(self.connect()?);
+        }
+        let res = f(&conn.as_ref().unwrap().1);
+        if res.is_err() {
+            *conn = None;
+        }
+        res
+    }
+
+    /**
+     * Download new files from the remote directory into the folder.
+     *
+     * @param settle Only take files whose size hasn't changed since the last listing
+     * @return Downloaded files
+     */
+    fn fetch_new(&self, conf: &BotConfig, settle: bool) -> BotResult<Vec<PathBuf>> {
+        self.with_sftp(|sftp| {
+            let listing = sftp.readdir(&self.dir).map_err(|e| anyhow!("Failed to list {:?}: {}", self.dir, e))?;
+            let mut sizes = self.sizes.lock().unwrap();
+            let last = std::mem::take(&mut *sizes);
+            let mut paths = Vec::new();
+            for (remote, stat) in listing {
+                let name = match remote.file_name() {
+                    Some(n) => n.to_string_lossy().to_string(),
+                    None => continue,
+                };
+                if !stat.is_file() || name.starts_with('.') {
+                    continue;
+                }
+                let path = conf.folder.join(&name);
+                if !conf.file_filter.accepts(&path) || self.files.lock().unwrap().values().any(|n| *n == name) {
+                    continue;
+                }
+                let size = stat.size.unwrap_or(0);
+                sizes.insert(name.clone(), size);
+                if settle && last.get(&name) != Some(&size) {
+                    debug!("Remote file {:?} is new or still growing, waiting for the next listing", name);
+                    continue;
+                }
+                if path.exists() {
+                    // Downloaded before a restart but not posted yet (see QUEUE_FILE); the queue skips it if it's gone
+                    info!("Already downloaded, not fetching again: {:?}", path);
+                    self.files.lock().unwrap().insert(path.clone(), name);
+                    paths.push(path);
+                    continue;
+                }
+                // Download under a hidden name, so a half-written file is never posted
+                let part = conf.folder.join(format!(".{}.part", name));
+                info!("Downloading sftp://{}{} ({} bytes)", self.host, remote.display(), size);
+                let res = std::fs::File::create(&part).map_err(BotError::from).and_then(|mut out| {
+                    let mut file = sftp.open(&remote).map_err(|e| anyhow!("Failed to open {:?}: {}", remote, e))?;
+                    std::io::copy(&mut file, &mut out)?;
+                    Ok(std::fs::rename(&part, &path)?)
+                });
+                if let Err(e) = res {
+                    let _ = std::fs::remove_file(&part);
+                    return Err(e);
+                }
+                self.files.lock().unwrap().insert(path.clone(), name);
+                paths.push(path);
+            }
+            Ok(paths)
+        })
+    }
+
+    /// Move the remote original of a posted or rejected file to `posted/` or `rejected/`
+    fn archive(&self, path: &Path, outcome: &str) -> BotResult<()> {
+        let name = match self.files.lock().unwrap().get(path) {
+            Some(name) => name.clone(),
+            None => return Ok(()),      // Not downloaded by us (e.g. --from-archive)
+        };
+        let (from, to_dir) = (self.dir.join(&name), self.dir.join(outcome));
+        info!("Moving sftp://{}{} to {}/", self.host, from.display(), to_dir.display());
+        self.with_sftp(|sftp| {
+            if sftp.stat(&to_dir).is_err() {
+                sftp.mkdir(&to_dir, 0o755).map_err(|e| anyhow!("Failed to create {:?}: {}", to_dir, e))?;
+            }
+            // Like local archiving, don't overwrite an earlier file of the same name
+            let mut to = to_dir.join(&name);
+            let mut n = 1;
+            while sftp.stat(&to).is_ok() {
+                to = to_dir.join(format!("{}.{}", name, n));
+                n += 1;
+            }
+            sftp.rename(&from, &to, None).map_err(|e| anyhow!("Failed to move {:?} to {:?}: {}", from, to, e))?;
+            Ok(())
+        })?;
+        self.files.lock().unwrap().remove(path);
+        Ok(())
+    }
+}
+
+impl Source for SftpSource {
+    fn prepare(&self, _conf: &BotConfig) -> BotResult<()> {
+        // Fail early on bad credentials or host keys, rather than in the poll loop
+        self.with_sftp(|_| Ok(()))
+    }
+
+    fn scan(&self, conf: &BotConfig) -> BotResult<Vec<PathBuf>> {
+        self.fetch_new(conf, false)
+    }
+
+    fn watch(&self, conf: &BotConfig, paths_tx: Sender<PathBuf>, toggles: Arc<control::Toggles>) -> BotResult<()> {
+        info!("Polling sftp://{}@{}{} every {:?}", self.user, self.host, self.dir.display(), self.poll_interval);
+        source::poll(self.poll_interval, paths_tx, toggles, || self.fetch_new(conf, true))
+    }
+
+    fn posted(&self, _conf: &BotConfig, path: &Path) -> BotResult<()> {
+        self.archive(path, "posted")
+    }
+
+    fn rejected(&self, _conf: &BotConfig, path: &Path) -> BotResult<()> {
+        self.archive(path, "rejected")
+    }
+}
+
+fn ssh_err(e: ssh2::Error) -> BotError {
+    anyhow!("SFTP: {}", e).into()
+}
@@ -0,0 +1,71 @@
+use std::path::{Path, PathBuf};
+use anyhow::anyhow;
+
+use crate::BotResult;
+
+/// Suffixes of metadata sidecar files, e.g. "report.pdf.meta.json"
+pub const SIDECAR_SUFFIXES: &[&str] = &[".meta.json", ".meta.yaml", ".meta.yml"];
+
+/**
+ * Per-file overrides from a metadata sidecar file.
+ */
+#[derive(Debug, Default, Clone)]
+pub struct FileMeta {
+    pub title: Option<String>,
+    pub initial_comment: Option<String>,
+    pub channel: Option<String>,
+    pub thread_ts: Option<String>,
+    pub icon: Option<String>,
+}
+
+/**
+ * If `path` is itself a sidecar file, return the path of the file it describes.
+ */
+pub fn target_of(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+    SIDECAR_SUFFIXES.iter()
+        .find_map(|s| name.strip_suffix(s))
+        .filter(|base| !base.is_empty())
+        .map(|base| path.with_file_name(base))
+}
+
+/**
+ * Find the sidecar file for a file, if there is one.
+ */
+pub fn find(path: &Path) -> Option<PathBuf> {
+    SIDECAR_SUFFIXES.iter()
+        .map(|s| {
+            let mut p = path.as_os_str().to_owned();
+            p.push(s);
+            PathBuf::from(p)
+        })
+        .find(|p| p.is_file())
+}
+
+/**
+ * Read and parse a sidecar file (JSON or YAML, by extension).
+ *
+ * Example:
+ *   {"title": "Q2 report", "initial_comment": "Final version", "channel": "#finance",
+ *    "thread_ts": "1717231234.000100", "icon": ":chart_with_upwards_trend:"}
+ */
+pub fn parse(sidecar: &Path) -> BotResult<FileMeta> {
+    let text = std::fs::read_to_string(sidecar)?;
+    let is_json = sidecar.extension().map(|e| e == "json").unwrap_or(false);
+    let json: serde_json::Value = if is_json {
+        serde_json::from_str(&text).map_err(|e| anyhow!("Invalid sidecar {:?}: {}", sidecar, e))?
+    } else {
+        serde_yaml::from_str(&text).map_err(|e| anyhow!("Invalid sidecar {:?}: {}", sidecar, e))?
+    };
+    if !json.is_object() {
+        return Err(anyhow!("Invalid sidecar {:?}: expected an object", sidecar).into());
+    }
+    let get = |key: &str| json[key].as_str().map(|s| s.to_string());
+    Ok(FileMeta {
+        title: get("title"),
+        initial_comment: get("initial_comment"),
+        channel: get("channel"),
+        thread_ts: get("thread_ts"),
+        icon: get("icon"),
+    })
+}
@@ -0,0 +1,115 @@
+use std::{path::{Path, PathBuf}, sync::{Arc, mpsc::Sender}, time::{Duration, Instant}};
+use log::{info, error};
+
+use crate::{BotConfig, BotError, BotResult, Tree, control, count_dirs, file_watcher};
+
+/**
+ * Where a section's files come from. Sources hand over local paths in the
+ * section's folder (remote backends download into it first), so the settle,
+ * queue and post logic is the same for all of them.
+ */
+pub trait Source: std::fmt::Debug + Send + Sync {
+    /**
+     * Check that the source can be watched, before the watcher thread starts.
+     */
+    fn prepare(&self, _conf: &BotConfig) -> BotResult<()> {
+        Ok(())
+    }
+
+    /**
+     * Files available right now (for `--once`). Only regular files, unfiltered.
+     */
+    fn scan(&self, conf: &BotConfig) -> BotResult<Vec<PathBuf>>;
+
+    /**
+     * Send new files to `paths_tx` as they appear, filtered by the section's
+     * `include` / `exclude`. Blocks until the section is stopped or `paths_tx` closes.
+     */
+    fn watch(&self, conf: &BotConfig, paths_tx: Sender<PathBuf>, toggles: Arc<control::Toggles>) -> BotResult<()>;
+
+    /**
+     * A file from this source was posted, before it's archived locally
+     * (`on_success`). Remote backends archive the original here.
+     */
+    fn posted(&self, _conf: &BotConfig, _path: &Path) -> BotResult<()> {
+        Ok(())
+    }
+
+    /**
+     * A file from this source was rejected, before it's moved to `rejected_dir`.
+     */
+    fn rejected(&self, _conf: &BotConfig, _path: &Path) -> BotResult<()> {
+        Ok(())
+    }
+}
+
+/**
+ * The section's `folder` on the local filesystem, watched with native file
+ * events or polling (`watch_mode`), and recursively if `recursive = true`.
+ */
+#[derive(Debug)]
+pub struct LocalFolder;
+
+impl Source for LocalFolder {
+    fn prepare(&self, conf: &BotConfig) -> BotResult<()> {
+        if conf.recursive {
+            let dirs = count_dirs(&conf.folder, conf.max_watched_dirs);
+            if dirs > conf.max_watched_dirs {
+                return Err(BotError::InvalidConfig(format!("Folder {:?} has over {} subdirectories (max_watched_dirs), not watching it recursively",
+                    conf.folder, conf.max_watched_dirs)));
+            }
+        }
+        Ok(())
+    }
+
+    fn scan(&self, conf: &BotConfig) -> BotResult<Vec<PathBuf>> {
+        info!("Scanning folder {:?}", conf.folder);
+        let entries: Vec<std::fs::DirEntry> = match Tree::for_section(conf) {
+            Some(tree) => {
+                let mut entries = Vec::new();
+                tree.walk(&conf.folder, &conf.folder, &mut entries)?;
+                entries
+            },
+            None => std::fs::read_dir(&conf.folder)?.filter_map(|e| e.ok()).collect(),
+        };
+        Ok(entries.into_iter()
+            .filter(|e| e.file_type().ok().map(|t| t.is_file()).unwrap_or(false))
+            .map(|e| e.path())
+            .collect())
+    }
+
+    fn watch(&self, conf: &BotConfig, paths_tx: Sender<PathBuf>, toggles: Arc<control::Toggles>) -> BotResult<()> {
+        file_watcher(conf.folder.clone(), paths_tx, toggles, conf.file_filter.clone(), Tree::for_section(conf), conf.watch_mode, conf.poll_interval)
+            .map_err(BotError::from)
+    }
+}
+
+/**
+ * Polling loop for remote sources: call `fetch` (which downloads new files
+ * into the folder) every `interval` and send what it got to `paths_tx`,
+ * until the section is stopped or `paths_tx` closes. Failed polls are logged
+ * and tried again on the next round.
+ */
+pub fn poll(interval: Duration, paths_tx: Sender<PathBuf>, toggles: Arc<control::Toggles>,
+    mut fetch: impl FnMut() -> BotResult<Vec<PathBuf>>) -> BotResult<()>
+{
+    let mut last_poll: Option<Instant> = None;
+    toggles.ready.store(true, std::sync::atomic::Ordering::Relaxed);
+    loop {
+        if toggles.stop.load(std::sync::atomic::Ordering::Relaxed) {
+            return Ok(());
+        }
+        if last_poll.map(|t| t.elapsed() >= interval).unwrap_or(true) {
+            last_poll = Some(Instant::now());
+            match fetch() {
+                Ok(paths) => for path in paths {
+                    if paths_tx.send(path).is_err() {
+                        return Ok(());
+                    }
+                },
+                Err(e) => error!("Polling for new files failed: {:?}", e),
+            }
+        }
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
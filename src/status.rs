@@ -0,0 +1,117 @@
+use std::path::Path;
+use log::{info, warn};
+use anyhow::anyhow;
+
+use crate::{BotConfig, BotError, BotResult, check_slack_response, icon_param, slack_client, with_retries};
+
+/// Default `status_template`
+pub const DEFAULT_TEMPLATE: &str = "Last drop: {date} {time}, {count} file(s)";
+
+/// Where to show the latest drop (`status_update`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusUpdate {
+    Topic,      // Channel topic (conversations.setTopic)
+    Message,    // A status message, posted once and then edited (chat.update)
+}
+
+impl std::str::FromStr for StatusUpdate {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "topic" => Ok(StatusUpdate::Topic),
+            "message" => Ok(StatusUpdate::Message),
+            _ => Err(()),
+        }
+    }
+}
+
+/**
+ * Render the `status_template` for a drop.
+ *
+ * Placeholders: {date}, {time}, {count}, {last_file}, {section}
+ */
+fn render(conf: &BotConfig, template: &str, files: &[String]) -> BotResult<String>
+{
+    let now = chrono::Local::now();
+    crate::template::render(template, |name| Ok::<_, BotError>(Some(match name {
+        "date" => now.format("%Y-%m-%d").to_string(),
+        "time" => now.format("%H:%M").to_string(),
+        "count" => files.len().to_string(),
+        "last_file" => files.last().cloned().unwrap_or_default(),
+        "section" => conf.section.clone(),
+        _ => return Ok(None),
+    })))
+}
+
+/**
+ * Show the latest drop in the channel topic or status message, as an
+ * at-a-glance freshness indicator for channel members.
+ *
+ * Status message state file contents: "<channel id> <message ts>"
+ *
+ * @param conf Bot configuration
+ * @param mode Topic or status message
+ * @param files Names of the files posted in the drop
+ * @param state_file Where to remember the status message
+ */
+pub fn update(conf: &BotConfig, mode: StatusUpdate, files: &[String], state_file: &Path) -> BotResult<()>
+{
+    let text = render(conf, conf.status_template.as_deref().unwrap_or(DEFAULT_TEMPLATE), files)?;
+    match mode {
+        StatusUpdate::Topic => {
+            let channel_id = conf.slack_lookup.channel_id(&conf.slack_channel)?
+                .ok_or_else(|| anyhow!("Channel not found, can't set its topic: {:?}", conf.slack_channel))?;
+            with_retries(conf, || call(conf, "conversations.setTopic", &[("channel", channel_id.as_str()), ("topic", text.as_str())]))?;
+            info!("Updated channel topic for {:?}: {}", conf.section, text);
+            Ok(())
+        },
+        StatusUpdate::Message => {
+            if let Some((channel_id, ts)) = read_state(state_file) {
+                match with_retries(conf, || call(conf, "chat.update", &[("channel", channel_id.as_str()), ("ts", ts.as_str()), ("text", text.as_str())])) {
+                    Ok(_) => {
+                        info!("Updated status message for {:?}: {}", conf.section, text);
+                        return Ok(());
+                    },
+                    Err(BotError::SlackApiError(e)) if e == "message_not_found" || e == "channel_not_found" => {
+                        warn!("Status message is gone ({}), posting a new one", e);
+                    },
+                    Err(e) => return Err(e),
+                }
+            }
+            let mut params = vec![
+                ("channel", conf.slack_channel.clone()),
+                ("username", conf.bot_name.clone()),
+                ("text", text.clone()),
+            ];
+            if let Some(icon) = &conf.bot_icon {
+                let (key, val) = icon_param(icon);
+                params.push((key, val));
+            }
+            let params: Vec<(&str, &str)> = params.iter().map(|(k, v)| (*k, v.as_str())).collect();
+            let json = with_retries(conf, || call(conf, "chat.postMessage", &params))?;
+            let channel_id = json["channel"].as_str().ok_or(BotError::SlackApiError("No channel in response".into()))?;
+            let ts = json["ts"].as_str().ok_or(BotError::SlackApiError("No ts in response".into()))?;
+            info!("Posted status message for {:?}: {}", conf.section, text);
+            std::fs::write(state_file, format!("{} {}", channel_id, ts))?;
+            Ok(())
+        },
+    }
+}
+
+fn call(conf: &BotConfig, method: &str, params: &[(&str, &str)]) -> BotResult<serde_json::Value>
+{
+    let _permit = conf.http_semaphore.acquire();
+    let res = slack_client(&conf.extra_headers).build()?
+        .post(format!("https://slack.com/api/{}", method))
+        .form(params)
+        .bearer_auth(conf.slack_token.get())
+        .send()?;
+    check_slack_response(res)
+}
+
+fn read_state(state_file: &Path) -> Option<(String, String)>
+{
+    let content = std::fs::read_to_string(state_file).ok()?;
+    let (channel_id, ts) = content.trim().split_once(' ')?;
+    Some((channel_id.to_string(), ts.to_string()))
+}
@@ -0,0 +1,86 @@
+#[cfg(unix)]
+use std::{sync::atomic::Ordering, time::Duration};
+#[cfg(unix)]
+use log::{info, debug, warn};
+
+use crate::control::SectionToggles;
+
+/**
+ * Tell systemd (`Type=notify`) when the service is ready, and keep its
+ * watchdog (`WatchdogSec=`) fed while every section's bot loop keeps
+ * checking in. Does nothing when not started by systemd.
+ *
+ * READY=1 is sent once all sections are watching for files (or have
+ * failed to start). WATCHDOG=1 pings stop when a running section hasn't
+ * been through its loop for the whole watchdog interval, e.g. stuck on a
+ * dead connection, so systemd restarts the service.
+ */
+#[cfg(unix)]
+pub fn start(sections: SectionToggles)
+{
+    if std::env::var_os("NOTIFY_SOCKET").is_none() {
+        return;
+    }
+    std::thread::spawn(move || {
+        while !sections.lock().unwrap().values().all(|t| t.ready.load(Ordering::Relaxed)) {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        let count = sections.lock().unwrap().len();
+        notify(&[sd_notify::NotifyState::Ready, sd_notify::NotifyState::Status(&format!("Watching {} section(s)", count))]);
+        info!("Notified systemd that the service is ready");
+
+        let mut usec = 0;
+        if !sd_notify::watchdog_enabled(false, &mut usec) {
+            return;
+        }
+        let timeout = Duration::from_micros(usec);
+        info!("systemd watchdog enabled, every section must check in within {:?}", timeout);
+        loop {
+            std::thread::sleep(timeout / 2);
+            let now = crate::control::unix_now();
+            let stuck: Vec<String> = sections.lock().unwrap().iter()
+                .filter(|(_, t)| !t.stop.load(Ordering::Relaxed))
+                .filter(|(_, t)| {
+                    let beat = t.heartbeat.load(Ordering::Relaxed);
+                    beat != 0 && now.saturating_sub(beat) > timeout.as_secs()
+                })
+                .map(|(name, _)| name.clone())
+                .collect();
+            if stuck.is_empty() {
+                debug!("Pinging systemd watchdog");
+                notify(&[sd_notify::NotifyState::Watchdog]);
+            } else {
+                warn!("Section(s) {:?} stuck for over {:?}, not pinging the systemd watchdog", stuck, timeout);
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn start(_sections: SectionToggles)
+{
+}
+
+/**
+ * Tell systemd the service is shutting down.
+ */
+#[cfg(unix)]
+pub fn stopping()
+{
+    if std::env::var_os("NOTIFY_SOCKET").is_some() {
+        notify(&[sd_notify::NotifyState::Stopping]);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn stopping()
+{
+}
+
+#[cfg(unix)]
+fn notify(states: &[sd_notify::NotifyState])
+{
+    if let Err(e) = sd_notify::notify(false, states) {
+        warn!("Failed to notify systemd: {}", e);
+    }
+}
@@ -0,0 +1,139 @@
+use std::path::Path;
+
+use crate::{BotResult, hashing::{self, HashAlgorithm}};
+
+/**
+ * Replace `{name}` placeholders in a template with values from `lookup`.
+ * Unknown placeholders (lookup returns None) are left as-is.
+ *
+ * @param template Template string, e.g. "New file: {filename} ({size_human})"
+ * @param lookup Function that returns the value for a placeholder name
+ */
+pub fn render<E, F>(template: &str, mut lookup: F) -> Result<String, E>
+    where F: FnMut(&str) -> Result<Option<String>, E>
+{
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        match lookup(&rest[1..end])? {
+            Some(val) => out.push_str(&val),
+            None => out.push_str(&rest[..=end]),
+        }
+        rest = &rest[end+1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/**
+ * Format a byte count for humans, e.g. "1.5 MB" (1024-based).
+ */
+pub fn human_size(bytes: u64) -> String
+{
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/**
+ * Human-friendly version of a file name stem: underscores and dashes
+ * become spaces and words are capitalized ("quarterly_sales-report" ->
+ * "Quarterly Sales Report").
+ */
+pub fn prettify(stem: &str) -> String {
+    stem.split(|c: char| c == '_' || c == '-' || c.is_whitespace())
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut chars = w.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/**
+ * Placeholder values for a file being posted. Expensive ones
+ * (like the checksum) are only computed if a template asks for them.
+ */
+pub struct FileVars<'a> {
+    path: &'a Path,
+    folder: &'a Path,
+    icon: Option<&'a str>,
+    hash_algorithm: HashAlgorithm,
+    hash_cache: bool,
+    hashes: Vec<(HashAlgorithm, String)>,
+}
+
+impl<'a> FileVars<'a> {
+    pub fn new(path: &'a Path, folder: &'a Path) -> Self {
+        FileVars { path, folder, icon: None, hash_algorithm: HashAlgorithm::Blake3, hash_cache: false, hashes: Vec::new() }
+    }
+
+    /**
+     * Set the algorithm of {hash}, and whether to use the xattr hash cache.
+     */
+    pub fn with_hashing(mut self, algorithm: HashAlgorithm, cache: bool) -> Self {
+        self.hash_algorithm = algorithm;
+        self.hash_cache = cache;
+        self
+    }
+
+    fn hash(&mut self, algorithm: HashAlgorithm) -> BotResult<String> {
+        if let Some((_, hex)) = self.hashes.iter().find(|(a, _)| *a == algorithm) {
+            return Ok(hex.clone());
+        }
+        let hex = hashing::hash_file(self.path, algorithm, self.hash_cache)?;
+        self.hashes.push((algorithm, hex.clone()));
+        Ok(hex)
+    }
+
+    /**
+     * Set the value of {icon} (empty if not set).
+     */
+    pub fn with_icon(mut self, icon: Option<&'a str>) -> Self {
+        self.icon = icon;
+        self
+    }
+
+    /**
+     * Get value for a placeholder:
+     * {filename}, {stem}, {pretty_name}, {ext}, {size}, {size_human}, {mtime}, {folder}, {sha256}, {hash}, {icon}
+     */
+    pub fn get(&mut self, name: &str) -> BotResult<Option<String>> {
+        Ok(Some(match name {
+            "filename" => self.path.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default(),
+            "stem" => self.path.file_stem().map(|f| f.to_string_lossy().to_string()).unwrap_or_default(),
+            "pretty_name" => prettify(&self.path.file_stem().map(|f| f.to_string_lossy()).unwrap_or_default()),
+            "ext" => self.path.extension().map(|f| f.to_string_lossy().to_string()).unwrap_or_default(),
+            "size" => std::fs::metadata(self.path)?.len().to_string(),
+            "size_human" => human_size(std::fs::metadata(self.path)?.len()),
+            "mtime" => {
+                let mtime = std::fs::metadata(self.path)?.modified()?;
+                chrono::DateTime::<chrono::Local>::from(mtime).format("%Y-%m-%d %H:%M:%S").to_string()
+            },
+            "folder" => self.folder.to_string_lossy().to_string(),
+            "icon" => self.icon.unwrap_or_default().to_string(),
+            "sha256" => self.hash(HashAlgorithm::Sha256)?,
+            "hash" => self.hash(self.hash_algorithm)?,
+            _ => return Ok(None),
+        }))
+    }
+}
@@ -0,0 +1,67 @@
+use std::io::Write;
+use anyhow::anyhow;
+
+use crate::{BotConfig, BotResult, BotSlackMessage, NAME, VERSION, check_slack_response, manifest, post_message_with_retries, slack_client, with_retries};
+
+/**
+ * Get a link to a posted message with chat.getPermalink.
+ */
+fn permalink(conf: &BotConfig, channel: &str, ts: &str) -> BotResult<String>
+{
+    let json = with_retries(conf, || {
+        let _permit = conf.http_semaphore.acquire();
+        let res = slack_client(&conf.extra_headers).build()?
+            .get("https://slack.com/api/chat.getPermalink")
+            .query(&[("channel", channel), ("message_ts", ts)])
+            .bearer_auth(conf.slack_token.get())
+            .send()?;
+        check_slack_response(res)
+    })?;
+    Ok(json["permalink"].as_str().ok_or(anyhow!("No permalink in response"))?.to_string())
+}
+
+/**
+ * Post a test message (and optionally a small generated text file) to
+ * a section's channel, and print links to them.
+ *
+ * @param conf Section to test
+ * @param with_file Also upload a test file
+ */
+pub fn run(conf: &BotConfig, with_file: bool) -> BotResult<()>
+{
+    let host = std::env::var("HOSTNAME").or_else(|_| std::env::var("COMPUTERNAME")).unwrap_or_else(|_| "?".to_string());
+    let text = format!("This is a test message from {} {} on host `{}`, section `{}`.\nFiles dropped in `{}` will be posted here.",
+        NAME, VERSION, host, conf.section, conf.folder.display());
+    let res = post_message_with_retries(conf, &BotSlackMessage {
+        title: Some("Test message".to_string()),
+        text: Some(text),
+        ..Default::default()
+    })?;
+    match (res["channel"].as_str(), res["ts"].as_str()) {
+        (Some(channel), Some(ts)) => println!("Posted test message: {}", permalink(conf, channel, ts)?),
+        _ => println!("Posted test message (no link available, e.g. for webhooks)"),
+    }
+
+    if with_file {
+        let path = std::env::temp_dir().join(format!("{}-test-{}.txt", NAME, std::process::id()));
+        let mut f = std::fs::File::create(&path)?;
+        writeln!(f, "Test file from {} {}, section \"{}\".", NAME, VERSION, conf.section)?;
+        drop(f);
+        let res = post_message_with_retries(conf, &BotSlackMessage {
+            title: Some("Test file".to_string()),
+            file: Some(path.clone()),
+            ..Default::default()
+        });
+        let _ = std::fs::remove_file(&path);
+        let res = res?;
+        let file = &res["file"];
+        match file["permalink"].as_str() {
+            Some(link) => println!("Posted test file: {}", link),
+            None => match manifest::message_ref(&res, file) {
+                Some((channel, ts)) => println!("Posted test file: {}", permalink(conf, &channel, &ts)?),
+                None => println!("Posted test file (no link available)"),
+            },
+        }
+    }
+    Ok(())
+}
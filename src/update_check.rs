@@ -0,0 +1,81 @@
+use log::{info, warn};
+use anyhow::anyhow;
+
+use crate::{BotConfig, BotResult, BotSlackMessage, VERSION, post_message_with_retries};
+
+/// Latest release as reported by the release endpoint
+#[derive(Debug)]
+pub struct Release {
+    pub version: String,
+    pub security: bool,     // Contains security-relevant fixes
+    pub url: Option<String>,
+}
+
+/**
+ * Parse a version like "v1.2.3" into numeric components.
+ */
+fn parse_version(v: &str) -> Vec<u64> {
+    v.trim().trim_start_matches('v')
+        .split(|c| c == '.' || c == '-' || c == '+')
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+fn is_newer(remote: &str, local: &str) -> bool {
+    parse_version(remote) > parse_version(local)
+}
+
+/**
+ * Fetch the latest release from the endpoint. Accepts either
+ * `{"version": "1.2.3", "security": true, "url": "..."}` or a GitHub
+ * "latest release" response (`tag_name`, `html_url`; security if the
+ * release notes mention it).
+ */
+pub fn fetch_latest(url: &str) -> BotResult<Release> {
+    let res = reqwest::blocking::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .user_agent(format!("{}/{}", crate::NAME, VERSION))
+        .build()?
+        .get(url)
+        .send()?
+        .error_for_status()?;
+    let json: serde_json::Value = serde_json::from_str(&res.text()?)
+        .map_err(|e| anyhow!("Invalid release info from {}: {}", url, e))?;
+    let version = json["version"].as_str().or(json["tag_name"].as_str())
+        .ok_or(anyhow!("No version in release info from {}", url))?;
+    let security = json["security"].as_bool()
+        .unwrap_or_else(|| json["body"].as_str().map(|b| b.to_lowercase().contains("security")).unwrap_or(false));
+    let url = json["url"].as_str().or(json["html_url"].as_str()).map(|s| s.to_string());
+    Ok(Release { version: version.to_string(), security, url })
+}
+
+/**
+ * Check for a newer release and log it. Security releases are logged as
+ * warnings and, if `notify` is given, also posted to that section's channel.
+ *
+ * @param url Release endpoint
+ * @param notify Section to post security update notices to
+ */
+pub fn check(url: &str, notify: Option<&BotConfig>) -> BotResult<()> {
+    let release = fetch_latest(url)?;
+    if !is_newer(&release.version, VERSION) {
+        info!("Running the latest version ({})", VERSION);
+        return Ok(());
+    }
+    let link = release.url.as_deref().unwrap_or(url);
+    if !release.security {
+        info!("A newer version is available: {} (running {}): {}", release.version, VERSION, link);
+        return Ok(());
+    }
+    warn!("A newer version with security fixes is available: {} (running {}): {}", release.version, VERSION, link);
+    if let Some(conf) = notify {
+        post_message_with_retries(conf, &BotSlackMessage {
+            title: Some("Security update available".to_string()),
+            text: Some(format!("{} {} has security fixes (this host runs {}). Please upgrade: {}",
+                crate::NAME, release.version, VERSION, link)),
+            icon: Some(":warning:".to_string()),
+            ..Default::default()
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,242 @@
+use std::{io::Read, path::{Path, PathBuf}, sync::{Arc, Mutex, atomic::{AtomicBool, AtomicU64, Ordering}, mpsc::Sender}};
+use log::{info, debug, warn, error};
+use anyhow::anyhow;
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::{BotConfig, BotError, BotResult, FileFilter, control, secrets, source::{LocalFolder, Source}};
+
+/// Where the upload server writes files before handing them over (hidden, so not watched)
+const STAGING_DIR: &str = ".uploads";
+
+/// Sections currently taking uploads. Workers come and go on config reload.
+static ENDPOINTS: Mutex<Vec<Endpoint>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+/// Numbers staging files, so concurrent uploads of the same name don't share one
+static NEXT_PART: AtomicU64 = AtomicU64::new(0);
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+struct Endpoint {
+    id: u64,
+    path: String,
+    token: String,
+    section: String,
+    folder: PathBuf,
+    filter: FileFilter,
+    max_size: Option<u64>,
+    paths_tx: Sender<PathBuf>,
+}
+
+/**
+ * The section's folder, plus files uploaded to `upload_path` on the
+ * `--upload-listen` HTTP server (multipart POST with `upload_token` as a
+ * bearer token), which go straight into the section's queue. Lets scripts
+ * on other hosts push files without a shared filesystem.
+ */
+pub struct UploadEndpoint {
+    path: String,
+    token: String,
+}
+
+impl std::fmt::Debug for UploadEndpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "UploadEndpoint({}, <redacted>)", self.path)
+    }
+}
+
+impl UploadEndpoint {
+    /**
+     * Upload endpoint from a config section.
+     *
+     * @return None if the section has no upload_path
+     */
+    pub fn from_config(section: &ini::Properties) -> BotResult<Option<Self>> {
+        let get = |key: &str| section.get(key).map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let path = match get("upload_path") {
+            Some(p) if p.starts_with('/') && !p.contains(['?', '#']) => p.trim_end_matches('/').to_string(),
+            Some(p) => return Err(BotError::InvalidConfig(format!("Invalid upload_path (expected e.g. /upload/cats): {:?}", p))),
+            None => return Ok(None),
+        };
+        let token = secrets::interpolate(&get("upload_token").ok_or(BotError::InvalidConfig("upload_path needs upload_token".to_string()))?)?.0;
+        Ok(Some(UploadEndpoint { path, token }))
+    }
+}
+
+impl Source for UploadEndpoint {
+    fn prepare(&self, conf: &BotConfig) -> BotResult<()> {
+        LocalFolder.prepare(conf)
+    }
+
+    fn scan(&self, conf: &BotConfig) -> BotResult<Vec<PathBuf>> {
+        LocalFolder.scan(conf)
+    }
+
+    fn watch(&self, conf: &BotConfig, paths_tx: Sender<PathBuf>, toggles: Arc<control::Toggles>) -> BotResult<()> {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        {
+            let mut endpoints = ENDPOINTS.lock().unwrap();
+            if endpoints.iter().any(|e| e.path == self.path && e.section != conf.section) {
+                return Err(BotError::InvalidConfig(format!("upload_path {:?} is already used by another section", self.path)));
+            }
+            endpoints.retain(|e| e.path != self.path);     // Previous worker of the section (config reload)
+            endpoints.push(Endpoint {
+                id,
+                path: self.path.clone(),
+                token: self.token.clone(),
+                section: conf.section.clone(),
+                folder: conf.folder.clone(),
+                filter: conf.file_filter.clone(),
+                max_size: conf.max_file_size,
+                paths_tx: paths_tx.clone(),
+            });
+        }
+        if LISTENING.load(Ordering::Relaxed) {
+            info!("Taking uploads for {:?} at {}", conf.section, self.path);
+        } else {
+            warn!("Section {:?} has upload_path, but there's no --upload-listen; only watching its folder", conf.section);
+        }
+        let res = LocalFolder.watch(conf, paths_tx, toggles);
+        ENDPOINTS.lock().unwrap().retain(|e| e.id != id);
+        res
+    }
+}
+
+/**
+ * Serve upload endpoints over HTTP (`--upload-listen`). Blocks forever.
+ * Put it behind a TLS terminating reverse proxy if it's reachable from
+ * outside the host, the tokens are sent in the clear otherwise.
+ *
+ * @param addr Address to listen on, e.g. "127.0.0.1:8080"
+ */
+pub fn serve(addr: &str) -> BotResult<()>
+{
+    LISTENING.store(true, Ordering::Relaxed);
+    let server = Server::http(addr).map_err(|e| anyhow!("Failed to listen for uploads on {}: {}", addr, e))?;
+    info!("Listening for uploads on http://{}", addr);
+    for mut req in server.incoming_requests() {
+        std::thread::spawn(move || {
+            let (status, body) = match handle(&mut req) {
+                Ok(names) => (200, serde_json::json!({ "ok": true, "files": names })),
+                Err((status, e)) => {
+                    warn!("Upload to {} refused ({}): {}", req.url(), status, e);
+                    (status, serde_json::json!({ "ok": false, "error": e }))
+                },
+            };
+            let res = Response::from_string(body.to_string())
+                .with_status_code(status)
+                .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+            if let Err(e) = req.respond(res) {
+                debug!("Failed to send upload response: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/**
+ * Check an upload request and save its files.
+ *
+ * @return Names of the queued files, or HTTP status and error message
+ */
+fn handle(req: &mut Request) -> Result<Vec<String>, (u16, String)>
+{
+    if *req.method() != tiny_http::Method::Post {
+        return Err((405, "Only POST is supported".to_string()));
+    }
+    let header = |name: &str| req.headers().iter().find(|h| h.field.equiv(name)).map(|h| h.value.as_str().to_string());
+    let (auth, content_type) = (header("Authorization").unwrap_or_default(), header("Content-Type"));
+    let url_path = req.url().split('?').next().unwrap_or_default().trim_end_matches('/').to_string();
+    let (section, folder, filter, max_size, paths_tx) = {
+        let endpoints = ENDPOINTS.lock().unwrap();
+        let endpoint = endpoints.iter().find(|e| e.path == url_path).ok_or((404, "No such upload endpoint".to_string()))?;
+        if !token_matches(auth.strip_prefix("Bearer ").unwrap_or_default(), &endpoint.token) {
+            return Err((401, "Missing or wrong upload token".to_string()));
+        }
+        (endpoint.section.clone(), endpoint.folder.clone(), endpoint.filter.clone(), endpoint.max_size, endpoint.paths_tx.clone())
+    };
+    let boundary = content_type
+        .and_then(|ct| ct.split(';').map(|s| s.trim()).find_map(|s| s.strip_prefix("boundary=").map(|b| b.trim_matches('"').to_string())))
+        .ok_or((400, "Expected a multipart/form-data upload".to_string()))?;
+
+    let staging = folder.join(STAGING_DIR);
+    std::fs::create_dir_all(&staging).map_err(|e| (500, format!("Failed to create {:?}: {}", staging, e)))?;
+    let mut multipart = multipart::server::Multipart::with_body(req.as_reader(), boundary);
+    let mut names = Vec::new();
+    loop {
+        let mut field = match multipart.read_entry() {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return Err((400, format!("Bad multipart body: {}", e))),
+        };
+        let name = match &field.headers.filename {
+            Some(name) => name.rsplit(['/', '\\']).next().unwrap_or_default().trim().to_string(),
+            None => continue,       // Not a file
+        };
+        if name.is_empty() || name.starts_with('.') {
+            return Err((400, format!("Unusable file name: {:?}", name)));
+        }
+        if !filter.accepts(&folder.join(&name)) {
+            return Err((415, format!("{:?} doesn't match the section's include / exclude", name)));
+        }
+        let part = staging.join(format!("{}.{}.part", NEXT_PART.fetch_add(1, Ordering::Relaxed), name));
+        let saved = save(&mut field.data, &part, max_size);
+        let path = saved.and_then(|_| place(&part, &folder, &name));
+        let path = match path {
+            Ok(path) => path,
+            Err(e) => {
+                let _ = std::fs::remove_file(&part);
+                return Err(e);
+            },
+        };
+        info!("Received upload for {:?}: {:?}", section, path);
+        names.push(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+        if paths_tx.send(path).is_err() {
+            error!("Section {:?} stopped, uploaded file left in its folder", section);
+        }
+    }
+    if names.is_empty() {
+        return Err((400, "No files in upload".to_string()));
+    }
+    Ok(names)
+}
+
+/**
+ * Move a staged upload into the folder under a free name ("name", "name.1", ...).
+ * Hard linking fails if the name is taken, so concurrent uploads can't
+ * overwrite each other, and the file never appears half-written.
+ */
+fn place(part: &Path, folder: &Path, name: &str) -> Result<PathBuf, (u16, String)>
+{
+    let mut path = folder.join(name);
+    let mut n = 1;
+    loop {
+        match std::fs::hard_link(part, &path) {
+            Ok(()) => break,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                path = folder.join(format!("{}.{}", name, n));
+                n += 1;
+            },
+            Err(e) => return Err((500, format!("Failed to move upload into place: {}", e))),
+        }
+    }
+    let _ = std::fs::remove_file(part);
+    Ok(path)
+}
+
+/// Write an uploaded file, refusing it if it's over the section's `max_file_size`
+fn save(data: &mut impl Read, path: &Path, max_size: Option<u64>) -> Result<(), (u16, String)>
+{
+    let mut out = std::fs::File::create(path).map_err(|e| (500, format!("Failed to create {:?}: {}", path, e)))?;
+    let limit = max_size.map(|m| m + 1).unwrap_or(u64::MAX);
+    let written = std::io::copy(&mut data.take(limit), &mut out).map_err(|e| (400, format!("Upload failed: {}", e)))?;
+    match max_size {
+        Some(max) if written > max => Err((413, format!("File is over max_file_size ({} bytes)", max))),
+        _ => Ok(()),
+    }
+}
+
+/// Compare tokens without leaking the matching prefix length through timing
+fn token_matches(given: &str, expected: &str) -> bool
+{
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
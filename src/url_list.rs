@@ -0,0 +1,133 @@
+use std::{path::Path, time::Duration};
+use log::{info, warn};
+use anyhow::anyhow;
+
+use crate::{BotConfig, BotResult, BotSlackMessage, UrlLists, post_message_with_retries};
+
+/// Slack's limit for a section block's text
+const SECTION_MAX_LEN: usize = 3000;
+/// Slack's limit for blocks in one message
+const MAX_BLOCKS: usize = 50;
+
+/// Is this a link list (`.urls` / `.links`) rather than a file to upload?
+pub fn is_url_list(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.eq_ignore_ascii_case("urls") || e.eq_ignore_ascii_case("links"))
+        .unwrap_or(false)
+}
+
+/**
+ * Read a link list: one URL per line, optionally followed by a label.
+ * Empty lines and `#` comments are skipped, as are (with a warning) lines
+ * that don't start with an http(s) URL.
+ *
+ * @return Slack-formatted links, `<url>` or `<url|label>`
+ */
+fn read_links(path: &Path) -> BotResult<Vec<String>> {
+    let mut links = Vec::new();
+    for line in std::fs::read_to_string(path)?.lines().map(|l| l.trim()) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (url, label) = match line.split_once(char::is_whitespace) {
+            Some((url, label)) => (url, Some(label.trim())),
+            None => (line, None),
+        };
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            warn!("Skipping line that isn't a URL in {:?}: {:?}", path, line);
+            continue;
+        }
+        links.push(match label {
+            Some(label) => format!("<{}|{}>", url, escape(label)),
+            None => format!("<{}>", url),
+        });
+    }
+    Ok(links)
+}
+
+/// Escape the characters Slack's mrkdwn reserves for links and mentions
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Bold title and comment, one per line (either may be missing)
+fn heading(title: Option<&str>, text: Option<&str>) -> Option<String> {
+    let lines: Vec<String> = title.map(|t| format!("*{}*", t)).into_iter().chain(text.map(str::to_string)).collect();
+    if lines.is_empty() { None } else { Some(lines.join("\n")) }
+}
+
+/**
+ * Block Kit list of links: the title (and comment) in the first section,
+ * then bullet lines split into as many sections as needed.
+ */
+fn list_blocks(heading: Option<String>, links: &[String]) -> serde_json::Value {
+    let section = |text: String| serde_json::json!({ "type": "section", "text": { "type": "mrkdwn", "text": text } });
+    let mut blocks: Vec<serde_json::Value> = heading.into_iter().map(section).collect();
+    let mut chunk = String::new();
+    let mut shown = 0;
+    for link in links {
+        let line = format!("• {}\n", link);
+        if !chunk.is_empty() && chunk.len() + line.len() > SECTION_MAX_LEN {
+            if blocks.len() == MAX_BLOCKS - 2 {
+                break;      // Leave room for the last chunk and the "more" note
+            }
+            blocks.push(section(std::mem::take(&mut chunk)));
+        }
+        chunk.push_str(&line);
+        shown += 1;
+    }
+    if !chunk.is_empty() {
+        blocks.push(section(chunk));
+    }
+    if shown < links.len() {
+        blocks.push(serde_json::json!({ "type": "context",
+            "elements": [{ "type": "mrkdwn", "text": format!("...and {} more", links.len() - shown) }] }));
+    }
+    serde_json::Value::Array(blocks)
+}
+
+/**
+ * Post a link list, as one message per URL or as one combined list
+ * (see `url_lists`). Separate messages are spaced out to stay within
+ * `limit_uploads_per_minute`.
+ *
+ * @param title Rendered title of the file (None for title_style = none)
+ * @param text Rendered comment, if any
+ * @param base_msg Channel and thread to post in
+ * @return Response for the first message (used for threading addenda)
+ */
+pub fn post(path: &Path, conf: &BotConfig, title: Option<String>, text: Option<String>, base_msg: BotSlackMessage) -> BotResult<serde_json::Value> {
+    let links = read_links(path)?;
+    if links.is_empty() {
+        return Err(anyhow!("No URLs in {:?}", path).into());
+    }
+    if conf.url_lists == UrlLists::Combined {
+        info!("Posting {} URLs from {:?} as a list", links.len(), path);
+        return post_message_with_retries(conf, &BotSlackMessage {
+            text: Some(format!("{} links", links.len())),
+            blocks: Some(list_blocks(heading(title.as_deref(), text.as_deref()), &links)),
+            unfurl: Some(conf.unfurl_links),
+            ..base_msg
+        });
+    }
+
+    info!("Posting {} URLs from {:?} as separate messages", links.len(), path);
+    let pause = Duration::from_secs(60) / conf.limit_uploads_per_minute.get();
+    let heading = heading(title.as_deref(), text.as_deref());
+    let mut first = None;
+    for (i, link) in links.iter().enumerate() {
+        if i > 0 {
+            std::thread::sleep(pause);
+        }
+        let res = post_message_with_retries(conf, &BotSlackMessage {
+            text: Some(match (i, &heading) {
+                (0, Some(heading)) => format!("{}\n{}", heading, link),
+                _ => link.clone(),
+            }),
+            unfurl: Some(conf.unfurl_links),
+            ..base_msg.clone()
+        })?;
+        first.get_or_insert(res);
+    }
+    Ok(first.unwrap_or_default())
+}
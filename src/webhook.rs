@@ -0,0 +1,155 @@
+use log::{info, error};
+use anyhow::anyhow;
+use sha2::{Digest, Sha256};
+
+use crate::{BotConfig, BotError, BotResult, BotSlackMessage, destination::Destination, parse_headers, secrets};
+
+/// How files are sent to a generic webhook
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookFile {
+    Multipart,      // One multipart POST: "metadata" (JSON) and "file" parts
+    Presigned,      // POST the metadata, then PUT the file to the "upload_url" in the response
+}
+
+impl std::str::FromStr for WebhookFile {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "multipart" => Ok(WebhookFile::Multipart),
+            "presigned" => Ok(WebhookFile::Presigned),
+            _ => Err(()),
+        }
+    }
+}
+
+/**
+ * Arbitrary HTTP endpoint that gets each file and its metadata
+ * (`webhook_url`, `webhook_headers`, `webhook_file`), e.g. for ingestion
+ * by downstream systems.
+ */
+pub struct Webhook {
+    url: String,
+    headers: reqwest::header::HeaderMap,
+    file: WebhookFile,
+}
+
+impl std::fmt::Debug for Webhook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Webhook({}, {:?})", self.label(), self.file)
+    }
+}
+
+impl Webhook {
+    /**
+     * Webhook destination from a config section.
+     *
+     * @return None if the section has no webhook_url
+     */
+    pub fn from_config(section: &ini::Properties) -> BotResult<Option<Self>> {
+        let url = match section.get("webhook_url").map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            Some(url) => secrets::interpolate(url)?.0,
+            None => return Ok(None),
+        };
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(BotError::InvalidConfig("webhook_url isn't an http(s) URL".to_string()));
+        }
+        let file = match section.get("webhook_file").map(|s| s.trim()) {
+            None => WebhookFile::Multipart,
+            Some(s) => s.parse().map_err(|_| BotError::InvalidConfig(format!("Invalid webhook_file (expected multipart or presigned): {:?}", s)))?,
+        };
+        Ok(Some(Webhook { url, headers: parse_headers(section, "webhook_headers")?, file }))
+    }
+
+    fn metadata(&self, conf: &BotConfig, msg: &BotSlackMessage) -> BotResult<serde_json::Value> {
+        let mut meta = serde_json::json!({
+            "section": conf.section,
+            "bot_name": conf.bot_name,
+            "title": msg.title,
+            "text": msg.text,
+        });
+        if let Some(file) = &msg.file {
+            meta["filename"] = file.file_name().unwrap_or_default().to_string_lossy().into();
+            meta["size"] = std::fs::metadata(file)?.len().into();
+            meta["content_type"] = mime_guess::from_path(file).first_or_octet_stream().to_string().into();
+        }
+        Ok(meta)
+    }
+
+    fn post_json(&self, conf: &BotConfig, payload: &serde_json::Value) -> BotResult<serde_json::Value> {
+        let _permit = conf.http_semaphore.acquire();
+        let res = reqwest::blocking::Client::builder().build()?
+            .post(&self.url)
+            .headers(self.headers.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(payload.to_string())
+            .send()?;
+        check_response(res)
+    }
+}
+
+impl Destination for Webhook {
+    /// "webhook:<hash>", the URL may contain secrets
+    fn label(&self) -> String {
+        let hash = format!("{:x}", Sha256::digest(self.url.as_bytes()));
+        format!("webhook:{}", &hash[..8])
+    }
+
+    fn post_text(&self, conf: &BotConfig, msg: &BotSlackMessage) -> BotResult<serde_json::Value> {
+        info!("Posting message to {}: {:?}", self.label(), &msg);
+        let meta = self.metadata(conf, msg)?;
+        self.post_json(conf, &meta)?;
+        info!("Got Ok from {}", self.label());
+        Ok(serde_json::json!({ "ok": true }))
+    }
+
+    fn post_file(&self, conf: &BotConfig, msg: &BotSlackMessage) -> BotResult<serde_json::Value> {
+        let file = msg.file.as_ref().ok_or(anyhow!("No file to upload"))?;
+        info!("Posting file to {}: {:?}", self.label(), &msg);
+        let meta = self.metadata(conf, msg)?;
+        let client = reqwest::blocking::Client::builder().timeout(conf.upload_timeout).build()?;
+        match self.file {
+            WebhookFile::Multipart => {
+                let form = reqwest::blocking::multipart::Form::new()
+                    .part("metadata", reqwest::blocking::multipart::Part::text(meta.to_string()).mime_str("application/json")?)
+                    .part("file", reqwest::blocking::multipart::Part::file(file)?);
+                let _permit = conf.http_semaphore.acquire();
+                let res = client.post(&self.url)
+                    .headers(self.headers.clone())
+                    .multipart(form)
+                    .send()?;
+                check_response(res)?;
+            },
+            WebhookFile::Presigned => {
+                let res = self.post_json(conf, &meta)?;
+                let upload_url = res["upload_url"].as_str()
+                    .ok_or(BotError::DestinationError(format!("{}: no upload_url in response", self.label())))?;
+                let _permit = conf.http_semaphore.acquire();
+                let res = client.put(upload_url)
+                    .header(reqwest::header::CONTENT_TYPE, meta["content_type"].as_str().unwrap_or("application/octet-stream"))
+                    .body(std::fs::File::open(file)?)
+                    .send()?;
+                check_response(res)?;
+            },
+        }
+        info!("Got Ok from {}", self.label());
+        Ok(serde_json::json!({ "ok": true }))
+    }
+}
+
+/**
+ * Check a webhook response. Rate limits and server errors are returned as
+ * HTTP errors (retried), other failures with the response body.
+ * Returns the response as JSON, if it is.
+ */
+fn check_response(res: reqwest::blocking::Response) -> BotResult<serde_json::Value> {
+    let status = res.status();
+    if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(res.error_for_status().unwrap_err().into());
+    }
+    let body = res.text().unwrap_or_default();
+    if !status.is_success() {
+        error!("Webhook error response: {} {}", status, body);
+        return Err(BotError::DestinationError(format!("Webhook: {} ({})", body.trim(), status)));
+    }
+    Ok(serde_json::from_str(&body).unwrap_or_default())
+}